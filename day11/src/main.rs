@@ -1,13 +1,24 @@
-use std::{io::{BufRead, BufReader}, path, fmt::{Display, Write}, ops::Index};
+use std::{io::BufRead, path, fmt::Display, ops::Index};
+#[cfg(test)]
+use std::fmt::Write;
 
-use anyhow::{Error};
+use anyhow::{bail,Context,Error};
+
+mod queries;
+mod universe;
+use universe::{Expansion, Universe};
 
 const DEBUG:bool = cfg!(debug_assertions);
 
+// the grid the map started out as: copied and grown a line at a time for part 1,
+// then with the empty lines counted for part 2. Universe does both without it,
+// these stay to check it against
+#[cfg(test)]
 fn insert_row(row:usize, grid: &mut Vec<Vec<char>>) {
     grid.insert(row, grid[row].clone());
 }
 
+#[cfg(test)]
 fn insert_col(col:usize, grid: &mut Vec<Vec<char>>) {
     let mut row = 0;
     while row < grid.len() {
@@ -17,6 +28,7 @@ fn insert_col(col:usize, grid: &mut Vec<Vec<char>>) {
     }
 }
 
+#[cfg(test)]
 fn expand_universe(grid: &mut Vec<Vec<char>>) {    
     let mut row = 0;
     let mut col = 0;
@@ -41,6 +53,7 @@ fn expand_universe(grid: &mut Vec<Vec<char>>) {
     }
 }
 
+#[cfg(test)]
 fn expand_old_universe(grid: &Vec<Vec<char>>) -> (Vec<usize>,Vec<usize>) {
     let mut expanded_rows = Vec::new();
     let mut expanded_cols = Vec::new();
@@ -71,6 +84,7 @@ fn expand_old_universe(grid: &Vec<Vec<char>>) -> (Vec<usize>,Vec<usize>) {
     return expansions;
 }
 
+#[cfg(test)]
 fn compute_distances(grid:&Vec<Vec<char>>) -> Vec<usize> {
     let gxy: Vec<(usize,usize)> = grid.iter().enumerate().map(
         |(r_idx, row)| row.iter().enumerate().filter_map(
@@ -93,12 +107,9 @@ fn compute_distances(grid:&Vec<Vec<char>>) -> Vec<usize> {
     return dist;
 }
 
+#[cfg(test)]
 fn compute_distances_expanded(grid:&Vec<Vec<char>>, factor:usize, expansions:&(Vec<usize>,Vec<usize>)) -> Vec<usize> {
-    let gxy: Vec<(usize,usize)> = grid.iter().enumerate().map(
-        |(r_idx, row)| row.iter().enumerate().filter_map(
-            move |(c_idx, c)|(*c == '#').then(||(r_idx,c_idx))
-        )
-    ).flatten().collect();
+    let gxy = find_galaxies(grid);
 
     if DEBUG { eprintln!("FOUND GALAXIES: [{gxy:?}]") };
 
@@ -117,52 +128,161 @@ fn compute_distances_expanded(grid:&Vec<Vec<char>>, factor:usize, expansions:&(V
     return dist;
 }
 
-fn go(input:&mut dyn BufRead) -> Result<(),Error>{
-    // galactic observatory
-    // puzzle input, star map of galaxies
-    let mut lines = BufReader::new(input).lines();
+#[cfg(test)]
+fn find_galaxies(grid:&Vec<Vec<char>>) -> Vec<(usize,usize)> {
+    grid.iter().enumerate().map(
+        |(r_idx, row)| row.iter().enumerate().filter_map(
+            move |(c_idx, c)|(*c == '#').then(||(r_idx,c_idx))
+        )
+    ).flatten().collect()
+}
 
-    let mut grid = lines.map(|line|line.unwrap().chars().collect::<Vec<char>>()).collect::<Vec<_>>();
+#[derive(Default)]
+struct Config {
+    // every pair's distance, for debugging
+    pairs: bool,
+    // galaxy number (from 1) and how many of its nearest neighbours
+    nearest: Option<(usize,usize)>,
+    farthest: bool,
+    // bucket width
+    histogram: Option<usize>,
+    // each galaxy's total distance to the rest, and the medoid
+    totals: bool,
+    // more expansion factors to sum the distances for
+    factors: Vec<usize>,
+    // per empty line widths, one axis each, summed alongside the answers.
+    // an axis without them keeps the part 2 factor
+    row_weights: Option<Vec<usize>>,
+    col_weights: Option<Vec<usize>>,
+}
 
-    if DEBUG { eprintln!("grid: {:?}", Grid::from(&grid)) };
+// "10,100" as [10, 100]
+fn parse_list(value:&str) -> Result<Vec<usize>,Error> {
+    value.split(',').map(|f| f.trim().parse().with_context(|| format!("bad number '{f}'"))).collect()
+}
 
-    // expand the universe
-    let expansions = expand_old_universe(&mut grid);
+// the queries asked for in the config, printed ahead of the answer
+fn report(galaxies:&[(usize,usize)], config:&Config) -> Result<(),Error> {
+    if let Some((of, k)) = config.nearest {
+        if of == 0 || of > galaxies.len() {
+            bail!("no galaxy {of}, there are {}", galaxies.len());
+        }
+        for (n, d) in queries::nearest(galaxies, of - 1, k) {
+            println!("nearest {of} -> {}: {d}", n + 1);
+        }
+    }
+    if config.farthest {
+        if let Some((a, b, d)) = queries::farthest_pair(galaxies) {
+            println!("farthest {} -> {}: {d}", a + 1, b + 1);
+        }
+    }
+    if let Some(width) = config.histogram {
+        if width == 0 {
+            bail!("histogram buckets need a width of at least 1");
+        }
+        for (bucket, pairs) in queries::histogram(galaxies, width) {
+            println!("{bucket:>12} {pairs}");
+        }
+    }
+    if config.totals {
+        for (n, total) in queries::totals(galaxies).iter().enumerate() {
+            println!("total {}: {total}", n + 1);
+        }
+        if let Some((m, total)) = queries::medoid(galaxies) {
+            println!("medoid {}: {total}", m + 1);
+        }
+    }
+    Ok(())
+}
 
-    if DEBUG { eprintln!("expanded: {:?}", Grid::from(&grid)) };
+fn go(input:&mut dyn BufRead, config:&Config) -> Result<(usize,usize),Error>{
+    // galactic observatory
+    // puzzle input, star map of galaxies
+    let universe = Universe::read(input)?;
 
-    // compute the distances
-    let dist = compute_distances_expanded(&grid, 1_000_000, &expansions);
+    // expand the universe, empty lines are twice as wide
+    let expansion = Expansion::Factor(2);
 
-    if DEBUG { eprintln!("dist: {:?}", &dist) };
+    // sum the distances
+    let steps = universe.sum_distances(&expansion, &expansion)?;
 
-    // find the farthest
-    let steps:usize = dist.iter().sum();
+    // output the steps required
+    println!("{steps}");
 
     // PART TWO
     eprintln!("PART TWO");
 
-    // follow the map steps
+    // and now a million times as wide
+    let part2_expansion = Expansion::Factor(1_000_000);
 
-    // output the steps required
-    println!("{steps}");
+    // every pair's distance, only when asked for: it's quadratic in the galaxies
+    if config.pairs {
+        let dist = universe.distances(&part2_expansion, &part2_expansion)?;
+        eprintln!("dist: {:?}", &dist);
+    }
+
+    report(&universe.expanded(&part2_expansion, &part2_expansion)?, config)?;
+
+    for &factor in config.factors.iter() {
+        let expansion = Expansion::Factor(factor);
+        println!("factor {factor}: {}", universe.sum_distances(&expansion, &expansion)?);
+    }
+
+    if config.row_weights.is_some() || config.col_weights.is_some() {
+        let weighted = |weights:&Option<Vec<usize>>| weights.clone().map_or(part2_expansion.clone(), Expansion::Weights);
+        let (rows, cols) = (weighted(&config.row_weights), weighted(&config.col_weights));
+        println!("weighted: {}", universe.sum_distances(&rows, &cols)?);
+    }
+
+    let part2_steps = universe.sum_distances(&part2_expansion, &part2_expansion)?;
 
-    return Ok(());
+    println!("{part2_steps}");
+
+    return Ok((steps, part2_steps));
 }
 
 fn main() -> Result<(),Error> {
-    go(&mut std::io::stdin().lock())
+    // --pairs and the queries up to --totals all measure the universe at the part 2 factor
+    // --pairs: the distance between every pair of galaxies, for debugging
+    // --nearest N K: galaxy N's K nearest galaxies, numbered from 1
+    // --farthest: the pair furthest apart
+    // --histogram WIDTH: pair counts by distance, in buckets WIDTH wide
+    // --totals: each galaxy's total distance to the others, and the medoid
+    // --factors 10,100: the distance sum for each of these expansion factors too
+    // --row-weights 2,5 / --col-weights 1,0,3: the distance sum with each empty row or column
+    //   this many lines wide, one weight per empty line in order
+    let mut config = Config::default();
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        let mut value = || args.next().with_context(|| format!("missing value for {arg}"));
+        match arg.as_str() {
+            "--pairs" => config.pairs = true,
+            "--nearest" => config.nearest = Some((value()?.parse()?, value()?.parse()?)),
+            "--farthest" => config.farthest = true,
+            "--histogram" => config.histogram = Some(value()?.parse()?),
+            "--totals" => config.totals = true,
+            "--factors" => config.factors = parse_list(&value()?)?,
+            "--row-weights" => config.row_weights = Some(parse_list(&value()?)?),
+            "--col-weights" => config.col_weights = Some(parse_list(&value()?)?),
+            _ => bail!("unknown argument {arg}"),
+        }
+    }
+    go(&mut std::io::stdin().lock(), &config)?;
+    Ok(())
 }
 
+#[cfg(test)]
 #[derive(PartialEq)]
 struct Grid<'a,T:PartialEq> (&'a Vec<Vec<T>>);
 
+#[cfg(test)]
 impl<'a, T:PartialEq> From<&'a Vec<Vec<T>>> for Grid<'a,T> {
     fn from(g: &'a Vec<Vec<T>>) -> Self {
         Self(g)
     }
 }
 
+#[cfg(test)]
 impl<'a> std::fmt::Debug for Grid<'a, char> {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         f.write_char('\n')?;
@@ -191,7 +311,18 @@ r"...#......
 .......#..
 #...#.....";
 
-    go(&mut testinput.as_bytes())
+    assert_eq!(go(&mut testinput.as_bytes(), &Config::default())?, (374, 82000210));
+    let config = Config { pairs: true, nearest: Some((5, 3)), farthest: true, histogram: Some(100), totals: true, factors: vec![1, 10, 100],
+        row_weights: Some(vec![2, 2]), col_weights: None };
+    assert_eq!(go(&mut testinput.as_bytes(), &config)?, (374, 82000210));
+    // weights have to match the empty lines, two rows and three columns
+    let config = Config { col_weights: Some(vec![2, 2]), ..Config::default() };
+    assert!(go(&mut testinput.as_bytes(), &config).is_err());
+    let config = Config { histogram: Some(0), ..Config::default() };
+    assert!(go(&mut testinput.as_bytes(), &config).is_err());
+    assert_eq!(parse_list(" 1,20 ,3")?, [1, 20, 3]);
+    assert!(parse_list("1,,3").is_err());
+    Ok(())
 }
 
 #[test]
@@ -251,10 +382,12 @@ r"....#........
     let check_dist = compute_distances(&grid);
 
     eprintln!("{:?}", &check_dist);
+
+    assert_eq!(check_dist.iter().sum::<usize>(), 374);
 }
 
 #[test]
-fn testinput3() {
+fn testinput3() -> Result<(),Error> {
     let testinput = 
 r"...#......
 .......#..
@@ -271,27 +404,19 @@ r"...#......
 
     let expansions = expand_old_universe(&grid);
 
-    let check_dist = compute_distances_expanded(&grid, 1, &expansions);
-
-    eprintln!("Factor 1 {:?}", &check_dist);
-
-    let steps:usize = check_dist.iter().sum();
+    let universe = Universe::read(&mut testinput.as_bytes())?;
 
-    eprintln!("{steps}");
+    for (factor, expected) in [(2, 374), (10, 1030), (100, 8410)] {
+        let check_dist = compute_distances_expanded(&grid, factor, &expansions);
 
-    let check_dist = compute_distances_expanded(&grid, 10, &expansions);
+        let steps:usize = check_dist.iter().sum();
 
-    eprintln!("Factor 10 {:?}", &check_dist);
+        assert_eq!(steps, expected, "factor {factor}");
 
-    let steps:usize = check_dist.iter().sum();
-
-    eprintln!("{steps}");
-
-    let check_dist = compute_distances_expanded(&grid, 100, &expansions);
-
-    eprintln!("Factor 100 {:?}", &check_dist);
-
-    let steps:usize = check_dist.iter().sum();
+        let expansion = Expansion::Factor(factor);
+        assert_eq!(universe.sum_distances(&expansion, &expansion)?, expected, "factor {factor}");
+    }
 
-    eprintln!("{steps}");
+    Ok(())
 }
+
@@ -0,0 +1,130 @@
+// questions about an expanded universe, beyond the one big sum
+// all of these take galaxies as Universe::expanded returns them, and answer with indexes into that list
+
+use std::collections::BTreeMap;
+
+use crate::DEBUG;
+
+fn distance(g:(usize,usize), g2:(usize,usize)) -> usize {
+    g.0.abs_diff(g2.0) + g.1.abs_diff(g2.1)
+}
+
+// the k galaxies closest to galaxies[of], as (index, distance), closest first and ties by index
+pub fn nearest(galaxies:&[(usize,usize)], of:usize, k:usize) -> Vec<(usize,usize)> {
+    let mut others: Vec<(usize,usize)> = galaxies.iter().enumerate()
+        .filter(|&(n, _)| n != of)
+        .map(|(n, &g)| (distance(galaxies[of], g), n))
+        .collect();
+    // partition around the kth first, so only the k kept get sorted
+    if k < others.len() {
+        others.select_nth_unstable(k);
+        others.truncate(k);
+    }
+    others.sort_unstable();
+    others.into_iter().map(|(d, n)| (n, d)).collect()
+}
+
+// the two galaxies furthest apart and their distance, in one pass.
+// turned 45 degrees, |dr| + |dc| is the larger of |d(r+c)| and |d(r-c)|,
+// so the answer is at the extremes of one of those two sums
+pub fn farthest_pair(galaxies:&[(usize,usize)]) -> Option<(usize,usize,usize)> {
+    if galaxies.len() < 2 {
+        return None;
+    }
+    let sums: [fn((usize,usize)) -> i128; 2] = [|g| g.0 as i128 + g.1 as i128, |g| g.0 as i128 - g.1 as i128];
+    sums.iter().map(|sum| {
+        let lo = (0..galaxies.len()).min_by_key(|&n| sum(galaxies[n])).unwrap();
+        let hi = (0..galaxies.len()).max_by_key(|&n| sum(galaxies[n])).unwrap();
+        (lo.min(hi), lo.max(hi), distance(galaxies[lo], galaxies[hi]))
+    }).max_by_key(|pair| pair.2)
+}
+
+// how many pairs fall in each distance bucket, keyed by the bucket's smallest distance
+pub fn histogram(galaxies:&[(usize,usize)], width:usize) -> BTreeMap<usize,usize> {
+    let mut buckets = BTreeMap::new();
+    for (n, &g) in galaxies.iter().enumerate() {
+        for &g2 in galaxies.iter().skip(n+1) {
+            *buckets.entry(distance(g, g2) / width * width).or_insert(0) += 1;
+        }
+    }
+    buckets
+}
+
+// adds, for each galaxy, its distance along one axis to every other galaxy.
+// sorted, the one at position i is above the i before it and below the rest
+fn axis_totals(coords:impl Iterator<Item=usize>, totals:&mut [usize]) {
+    let mut sorted: Vec<(usize,usize)> = coords.enumerate().map(|(n, c)| (c, n)).collect();
+    sorted.sort_unstable();
+    let all: usize = sorted.iter().map(|s| s.0).sum();
+    let mut below = 0;
+    for (i, &(coord, n)) in sorted.iter().enumerate() {
+        let above = all - below - coord;
+        totals[n] += (coord*i - below) + (above - coord*(sorted.len() - 1 - i));
+        below += coord;
+    }
+}
+
+// each galaxy's total distance to all the others
+pub fn totals(galaxies:&[(usize,usize)]) -> Vec<usize> {
+    let mut totals = vec![0; galaxies.len()];
+    axis_totals(galaxies.iter().map(|g| g.0), &mut totals);
+    axis_totals(galaxies.iter().map(|g| g.1), &mut totals);
+    if DEBUG { eprintln!("TOTALS: {totals:?}") };
+    totals
+}
+
+// the galaxy with the smallest total distance to the rest, and that total
+pub fn medoid(galaxies:&[(usize,usize)]) -> Option<(usize,usize)> {
+    totals(galaxies).into_iter().enumerate().min_by_key(|&(_, total)| total)
+}
+
+#[cfg(test)]
+use crate::universe::{EXAMPLE, Expansion, Universe};
+
+#[cfg(test)]
+fn example() -> Vec<(usize,usize)> {
+    let universe = Universe::read(&mut EXAMPLE.as_bytes()).unwrap();
+    universe.expanded(&Expansion::Factor(2), &Expansion::Factor(2)).unwrap()
+}
+
+#[test]
+fn test_nearest() {
+    let galaxies = example();
+    let near = nearest(&galaxies, 4, 3);
+    assert_eq!(near.len(), 3);
+    assert!(near.windows(2).all(|w| w[0].1 <= w[1].1));
+    let mut brute: Vec<usize> = (0..galaxies.len()).filter(|&n| n != 4).map(|n| distance(galaxies[4], galaxies[n])).collect();
+    brute.sort();
+    assert_eq!(near.iter().map(|n| n.1).collect::<Vec<_>>(), brute[..3]);
+    assert_eq!(nearest(&galaxies, 4, 100).len(), 8);
+    assert_eq!(nearest(&galaxies, 4, 0), []);
+}
+
+#[test]
+fn test_farthest() {
+    let galaxies = example();
+    let (a, b, d) = farthest_pair(&galaxies).unwrap();
+    assert_eq!(d, distance(galaxies[a], galaxies[b]));
+    let brute = Universe::read(&mut EXAMPLE.as_bytes()).unwrap()
+        .distances(&Expansion::Factor(2), &Expansion::Factor(2)).unwrap();
+    assert_eq!(Some(&d), brute.iter().max());
+    assert_eq!(farthest_pair(&galaxies[..1]), None);
+}
+
+#[test]
+fn test_histogram_totals() {
+    let galaxies = example();
+    let buckets = histogram(&galaxies, 5);
+    assert_eq!(buckets.values().sum::<usize>(), 36);
+    assert!(buckets.keys().all(|k| k % 5 == 0));
+
+    let totals = totals(&galaxies);
+    for (n, &total) in totals.iter().enumerate() {
+        assert_eq!(total, galaxies.iter().map(|&g| distance(galaxies[n], g)).sum::<usize>());
+    }
+    // every pair counted from both ends
+    assert_eq!(totals.iter().sum::<usize>(), 2 * 374);
+    let (m, total) = medoid(&galaxies).unwrap();
+    assert_eq!(total, *totals.iter().min().unwrap());
+    assert_eq!(totals[m], total);
+}
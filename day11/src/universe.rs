@@ -0,0 +1,188 @@
+// the star map without all the empty space
+// galaxy coordinates and which rows and columns are empty, expanded on demand
+
+use std::io::{BufRead, BufReader};
+
+use anyhow::{bail, Error};
+
+use crate::DEBUG;
+
+// how wide each empty line grows
+#[derive(Debug, Clone, PartialEq)]
+pub enum Expansion {
+    // every empty line becomes `factor` lines
+    Factor(usize),
+    // the nth empty line becomes weights[n] lines, zero squeezes it out entirely
+    Weights(Vec<usize>),
+}
+
+impl Expansion {
+    // widths[k] is how wide the first k empty lines are together
+    fn widths(&self, empties:usize) -> Result<Vec<usize>,Error> {
+        let mut widths = Vec::with_capacity(empties + 1);
+        widths.push(0);
+        match self {
+            Expansion::Factor(factor) => widths.extend((1..=empties).map(|k| k*factor)),
+            Expansion::Weights(weights) => {
+                if weights.len() != empties {
+                    bail!("{} weights for {empties} empty lines", weights.len());
+                }
+                widths.extend(weights.iter().scan(0, |total, w| { *total += w; Some(*total) }));
+            },
+        }
+        Ok(widths)
+    }
+}
+
+#[derive(Debug)]
+pub struct Universe {
+    // (row, col), row major
+    pub galaxies: Vec<(usize,usize)>,
+    // sorted
+    pub empty_rows: Vec<usize>,
+    pub empty_cols: Vec<usize>,
+}
+
+// sum of |a - b| over every pair. once sorted, each coordinate is at least
+// everything before it, so it contributes coord*i minus the sum of those i
+pub fn sum_pairwise(mut coords:Vec<usize>) -> usize {
+    coords.sort_unstable();
+    let mut prefix = 0;
+    let mut total = 0;
+    for (i, &coord) in coords.iter().enumerate() {
+        total += coord*i - prefix;
+        prefix += coord;
+    }
+    total
+}
+
+// where each coordinate lands along one axis. the k empty lines before it
+// (a binary search, `empties` is sorted) count widths[k] instead of k
+fn expand_axis(coords:impl Iterator<Item=usize>, empties:&[usize], expansion:&Expansion) -> Result<Vec<usize>,Error> {
+    let widths = expansion.widths(empties.len())?;
+    Ok(coords.map(|coord| {
+        let k = empties.partition_point(|&e| e < coord);
+        coord - k + widths[k]
+    }).collect())
+}
+
+impl Universe {
+    // one pass over the map, keeping just the galaxies
+    pub fn read(input:&mut dyn BufRead) -> Result<Self,Error> {
+        let mut galaxies = Vec::new();
+        let mut empty_rows = Vec::new();
+        let mut used_cols: Vec<bool> = Vec::new();
+        for (row, line) in BufReader::new(input).lines().enumerate() {
+            let line = line?;
+            if used_cols.len() < line.len() {
+                used_cols.resize(line.len(), false);
+            }
+            let before = galaxies.len();
+            for (col, c) in line.bytes().enumerate() {
+                match c {
+                    b'#' => { galaxies.push((row, col)); used_cols[col] = true; },
+                    b'.' => (),
+                    _ => bail!("unexpected '{}' at row {row} col {col}", c as char),
+                }
+            }
+            if galaxies.len() == before {
+                empty_rows.push(row);
+            }
+        }
+        let empty_cols = used_cols.iter().enumerate().filter_map(|(col, &used)| (!used).then_some(col)).collect();
+        let universe = Universe { galaxies, empty_rows, empty_cols };
+        if DEBUG { eprintln!("UNIVERSE: {universe:?}") };
+        Ok(universe)
+    }
+
+    // the galaxies once the empty rows and columns have grown
+    pub fn expanded(&self, rows:&Expansion, cols:&Expansion) -> Result<Vec<(usize,usize)>,Error> {
+        let erows = expand_axis(self.galaxies.iter().map(|g| g.0), &self.empty_rows, rows)?;
+        let ecols = expand_axis(self.galaxies.iter().map(|g| g.1), &self.empty_cols, cols)?;
+        Ok(erows.into_iter().zip(ecols).collect())
+    }
+
+    // the sum of every pair's distance. rows and columns are independent under the
+    // manhattan distance, so each axis is sorted and summed on its own
+    pub fn sum_distances(&self, rows:&Expansion, cols:&Expansion) -> Result<usize,Error> {
+        let erows = expand_axis(self.galaxies.iter().map(|g| g.0), &self.empty_rows, rows)?;
+        let ecols = expand_axis(self.galaxies.iter().map(|g| g.1), &self.empty_cols, cols)?;
+        Ok(sum_pairwise(erows) + sum_pairwise(ecols))
+    }
+
+    // every pair's distance, in the same order as compute_distances. quadratic, for debugging
+    pub fn distances(&self, rows:&Expansion, cols:&Expansion) -> Result<Vec<usize>,Error> {
+        let gxy = self.expanded(rows, cols)?;
+        let mut dist = Vec::new();
+        for (n, g) in gxy.iter().enumerate() {
+            for g2 in gxy.iter().skip(n+1) {
+                dist.push(g.0.abs_diff(g2.0) + g.1.abs_diff(g2.1));
+            }
+        }
+        Ok(dist)
+    }
+}
+
+#[cfg(test)]
+pub const EXAMPLE: &str =
+r"...#......
+.......#..
+#.........
+..........
+......#...
+.#........
+.........#
+..........
+.......#..
+#...#.....";
+
+#[test]
+fn test_universe() -> Result<(),Error> {
+    let universe = Universe::read(&mut EXAMPLE.as_bytes())?;
+    assert_eq!(universe.galaxies.len(), 9);
+    assert_eq!(universe.empty_rows, [3, 7]);
+    assert_eq!(universe.empty_cols, [2, 5, 8]);
+
+    // the grid expanding part 1 and the sparse one agree
+    let mut grid = EXAMPLE.split_ascii_whitespace().map(|line|line.chars().collect::<Vec<char>>()).collect::<Vec<_>>();
+    crate::expand_universe(&mut grid);
+    let twice = Expansion::Factor(2);
+    assert_eq!(universe.distances(&twice, &twice)?, crate::compute_distances(&grid));
+
+    let grid = EXAMPLE.split_ascii_whitespace().map(|line|line.chars().collect::<Vec<char>>()).collect::<Vec<_>>();
+    let expansions = crate::expand_old_universe(&grid);
+    for factor in [1, 2, 10, 100, 1_000_000] {
+        let pairwise:usize = crate::compute_distances_expanded(&grid, factor, &expansions).iter().sum();
+        let expansion = Expansion::Factor(factor);
+        assert_eq!(universe.sum_distances(&expansion, &expansion)?, pairwise, "factor {factor}");
+        assert_eq!(universe.distances(&expansion, &expansion)?.iter().sum::<usize>(), pairwise, "factor {factor}");
+    }
+
+    assert_eq!(sum_pairwise(vec![5, 1, 3]), 4 + 2 + 2);
+    assert_eq!(sum_pairwise(vec![]), 0);
+    Ok(())
+}
+
+#[test]
+fn test_expansions() -> Result<(),Error> {
+    let universe = Universe::read(&mut EXAMPLE.as_bytes())?;
+    let (two, ten) = (Expansion::Factor(2), Expansion::Factor(10));
+
+    // each axis adds up on its own
+    assert_eq!(universe.sum_distances(&two, &ten)? + universe.sum_distances(&ten, &two)?,
+        universe.sum_distances(&two, &two)? + universe.sum_distances(&ten, &ten)?);
+    assert!(universe.sum_distances(&two, &ten)? != universe.sum_distances(&ten, &two)?);
+
+    // equal weights are just a factor
+    assert_eq!(universe.sum_distances(&Expansion::Weights(vec![10, 10]), &Expansion::Weights(vec![2, 2, 2]))?,
+        universe.sum_distances(&ten, &two)?);
+    assert!(universe.sum_distances(&Expansion::Weights(vec![10]), &two).is_err());
+
+    // weight zero squeezes a line out, and each line can differ
+    let universe = Universe::read(&mut "#..\n...\n..#\n".as_bytes())?;
+    assert_eq!(universe.expanded(&Expansion::Weights(vec![0]), &Expansion::Weights(vec![5]))?, [(0, 0), (1, 6)]);
+    assert_eq!(universe.sum_distances(&Expansion::Weights(vec![3]), &Expansion::Factor(1))?, 4 + 2);
+
+    assert!(Universe::read(&mut "#.x".as_bytes()).is_err());
+    Ok(())
+}
@@ -1,7 +1,9 @@
 use std::{io::{BufRead, BufReader}, path, fmt::{Display, Write}, ops::{Index, Range}};
 
-use anyhow::{Error};
-use regex::{Regex, RegexBuilder};
+use anyhow::{bail,Context,Error};
+mod nfa;
+#[cfg(test)]
+mod oracle;
 
 const DEBUG:bool = cfg!(debug_assertions);
 
@@ -14,90 +16,299 @@ fn str_to_vec(somestr: &str) -> (String,Vec<i32>) {
     return (String::from(left), vec);
 }
 
-// may need our own FSM to match these
-// do a recursive backtracking match
-pub fn match_groups(pos:usize, remaining: &str, groups: &[i32], matches:&mut Vec<Range<usize>>) {
-    let mut i = 0;
-    if DEBUG { eprintln!("in [{pos}..]'{remaining}', matching {groups:?} ") };
+// part 2 folds every row: the springs five times over joined by '?', the groups five times over
+fn unfold(springs:&str, groups:&[i32], times:usize) -> (String,Vec<i32>) {
+    (vec![springs; times].join("?"), groups.repeat(times))
+}
+
+// a group of len damaged springs can start at pos: none of its cells are known to be working,
+// and the cell after it, if there is one, isn't known to be damaged
+fn group_fits(springs:&[u8], working:&[usize], pos:usize, len:usize) -> bool {
+    let end = pos + len;
+    springs[pos] != b'.' && end <= springs.len() && working[end] == working[pos] && (end == springs.len() || springs[end] != b'#')
+}
+
+// count the arrangements without building any of them.
+// ways[pos][g] is how many ways groups[g..] fit into springs[pos..], filled in from the end.
+// at each position the spring is either working, or the start of the next group, which then
+// needs groups[g] cells that could be damaged and a cell after it that could be working
+struct Ways {
+    springs: Vec<u8>,
+    groups: Vec<usize>,
+    // working[i] counts the '.' before i, so a window is free of them in O(1)
+    working: Vec<usize>,
+    ways: Vec<Vec<u64>>,
+}
+
+impl Ways {
+    // the counts are exact or an error, never wrapped
+    fn new(springs:&str, groups:&[i32]) -> Result<Self,Error> {
+        let s = springs.as_bytes();
+        let groups: Vec<usize> = groups.iter().map(|&g| g as usize).collect();
+        let (n, m) = (s.len(), groups.len());
 
-    // consume the empty space, if any
-    while i < remaining.len() && remaining.as_bytes()[i] == b'.' {
-        i += 1;
+        let mut working = vec![0; n + 1];
+        for (i, &c) in s.iter().enumerate() {
+            working[i+1] = working[i] + (c == b'.') as usize;
+        }
+
+        // a group can end the row, so its separator lands at n, and the next position is n+1
+        let mut ways = vec![vec![0u64; m + 1]; n + 2];
+        ways[n][m] = 1;
+        ways[n+1][m] = 1;
+        for pos in (0..n).rev() {
+            for g in 0..=m {
+                let mut total: u64 = 0;
+                if s[pos] != b'#' {
+                    total = ways[pos+1][g];
+                }
+                if g < m && group_fits(s, &working, pos, groups[g]) {
+                    total = total.checked_add(ways[pos + groups[g] + 1][g+1])
+                        .with_context(|| format!("more than {} arrangements of {springs}", u64::MAX))?;
+                }
+                ways[pos][g] = total;
+            }
+        }
+        if DEBUG { eprintln!("{springs} {groups:?} -> {}", ways[0][0]) };
+        Ok(Ways { springs: s.to_vec(), groups, working, ways })
     }
 
-    if i == remaining.len() && groups.len() == 0 {
-        if DEBUG { eprintln!("matched ({pos}..{i}) ") };
-        matches.push(pos..i);
-        return;
+    fn count(&self) -> u64 {
+        self.ways[0][0]
     }
 
-    // consume the first "groups", if possible
-    let mut n = 0;
-    while i < remaining.len() && n < groups[0] && (remaining.as_bytes()[i] == b'#' || remaining.as_bytes()[i] == b'?') {
-        n += 1;
-        i += 1;
+    // arrangement number k, counting the ones with a working spring first at each choice.
+    // the table says how many arrangements are behind each choice, so the walk goes
+    // straight there without visiting the k before it
+    fn nth(&self, mut k:u64) -> Option<String> {
+        if k >= self.count() {
+            return None;
+        }
+        let n = self.springs.len();
+        let mut row = self.springs.clone();
+        let (mut pos, mut g) = (0, 0);
+        while pos < n {
+            if row[pos] != b'#' {
+                let working = self.ways[pos+1][g];
+                if k < working {
+                    row[pos] = b'.';
+                    pos += 1;
+                    continue;
+                }
+                k -= working;
+            }
+            // k is still under ways[pos][g], so the next group starts here
+            let end = pos + self.groups[g];
+            row[pos..end].fill(b'#');
+            if end < n {
+                row[end] = b'.';
+            }
+            pos = end + 1;
+            g += 1;
+        }
+        Some(String::from_utf8(row).unwrap())
+    }
+
+    // a page of arrangements, built one at a time as they're asked for
+    fn arrangements(&self, page:Range<u64>) -> impl Iterator<Item=String> + '_ {
+        (page.start..page.end.min(self.count())).map(|k| self.nth(k).unwrap())
+    }
+}
+
+fn count_arrangements(springs:&str, groups:&[i32]) -> Result<u64,Error> {
+    Ok(Ways::new(springs, groups)?.count())
+}
+
+const HIGHLIGHT: &str = "\x1b[1;33m";
+const PLAIN: &str = "\x1b[0m";
+
+// an arrangement to line up under its row, with the cells that were '?' and aren't any more highlighted
+fn render(springs:&str, arrangement:&str) -> String {
+    let mut line = String::new();
+    for (was, now) in springs.chars().zip(arrangement.chars()) {
+        if was == '?' && now != '?' {
+            write!(line, "{HIGHLIGHT}{now}{PLAIN}").unwrap();
+        } else {
+            line.push(now);
+        }
     }
+    line
+}
 
-    if n < groups[0] || (i < remaining.len() && remaining.as_bytes()[i] == b'#') {
-        if DEBUG { eprintln!("no match segment {} at {i}", groups[0]) };
-        return;
+// what every arrangement agrees on, like a nonogram line solver: each '?' that is '#' in all
+// of them, or '.' in all of them, is filled in, and the rest stay '?'. None if there are none.
+// the table counts the ways to finish from each (pos, g), and a forward pass counts the ways to
+// get there, so their product is how many arrangements take each step. adding up the steps that
+// leave a cell working says how many arrangements agree it's '.', and the rest have it '#'
+fn hints(springs:&str, groups:&[i32]) -> Result<Option<String>,Error> {
+    let table = Ways::new(springs, groups)?;
+    let total = table.count();
+    if total == 0 {
+        return Ok(None);
     }
+    let (s, ways) = (&table.springs, &table.ways);
+    let (n, m) = (s.len(), table.groups.len());
+
+    // only (pos, g) with a way to finish are followed, so no count here goes past total
+    let mut before = vec![vec![0u64; m + 1]; n + 2];
+    before[0][0] = 1;
+    let mut working = vec![0u64; n];
+    for pos in 0..n {
+        for g in 0..=m {
+            let here = before[pos][g];
+            if here == 0 {
+                continue;
+            }
+            if s[pos] != b'#' && ways[pos+1][g] > 0 {
+                before[pos+1][g] += here;
+                working[pos] += here * ways[pos+1][g];
+            }
+            if g < m && group_fits(s, &table.working, pos, table.groups[g]) && ways[pos + table.groups[g] + 1][g+1] > 0 {
+                let end = pos + table.groups[g];
+                before[end+1][g+1] += here;
+                // the working spring after the group
+                if end < n {
+                    working[end] += here * ways[end+1][g+1];
+                }
+            }
+        }
+    }
+
+    let hint = s.iter().zip(&working).map(|(&c, &w)| match c {
+        b'?' if w == 0 => '#',
+        b'?' if w == total => '.',
+        _ => c as char,
+    }).collect();
+    Ok(Some(hint))
+}
+
+// which counter answers, or both, and they have to agree
+#[derive(Clone, Copy, PartialEq)]
+enum Backend {
+    Table,
+    Nfa,
+    Both,
+}
 
-    if i == remaining.len() && n == groups[0] && groups.len() == 1{
-        if DEBUG { eprintln!("matched ({pos}..{i}) ") };
-        matches.push(pos..i);
-        return;
+impl Backend {
+    fn parse(name:&str) -> Result<Self,Error> {
+        match name {
+            "table" => Ok(Backend::Table),
+            "nfa" => Ok(Backend::Nfa),
+            "both" => Ok(Backend::Both),
+            _ => bail!("unknown backend {name}, expected table, nfa or both"),
+        }
     }
 
-    // must consume at least one separator
-    i += 1;
+    fn count(self, springs:&str, groups:&[i32]) -> Result<u64,Error> {
+        match self {
+            Backend::Table => count_arrangements(springs, groups),
+            Backend::Nfa => nfa::count_nfa(springs, groups),
+            Backend::Both => {
+                let (table, nfa) = (count_arrangements(springs, groups)?, nfa::count_nfa(springs, groups)?);
+                if table != nfa {
+                    bail!("counters disagree on {springs} {groups:?}: table {table}, nfa {nfa}");
+                }
+                Ok(table)
+            },
+        }
+    }
+}
 
-    // try to match the rest
-    match_groups(pos, &remaining[i..], &groups[1..], matches);
+struct Config {
+    // how many copies of each row part 2 joins up
+    unfold: usize,
+    // print these arrangements of each row, by number
+    show: Option<Range<u64>>,
+    backend: Backend,
+    // the cells of each row that only have one possible value
+    hints: bool,
 }
 
-fn build_regex(groups: &Vec<i32>) -> Regex {
-    let re_str:String = groups.iter().map(|i|format!("[#\\?]{{{i}}}")).collect::<Vec<String>>().join("[\\.\\?]+");
+impl Default for Config {
+    fn default() -> Self {
+        Config { unfold: 5, show: None, backend: Backend::Table, hints: false }
+    }
+}
 
-    if DEBUG { eprintln!("{groups:?} => {re_str}") };
-    //return Regex::new(&re_str).configure(Config::new().match_kind(MatchKind::All));
-    return Regex::new(&re_str).unwrap();
+// totals over the rows overflow the same way a single row's count can
+fn add_count(total:u64, count:u64) -> Result<u64,Error> {
+    total.checked_add(count).with_context(|| format!("total arrangements pass {}", u64::MAX))
 }
 
-fn go(input:&mut dyn BufRead) -> Result<(),Error>{
+fn go(input:&mut dyn BufRead, config:&Config) -> Result<(u64,u64),Error>{
     // spring repair
     // puzzle input, list of springs, list of spring groups.
     let mut lines = BufReader::new(input).lines();
     let mut result = 0;
+    let mut unfolded_result = 0;
 
     while let Some(Ok(line)) = lines.next() {
         let (springs,groups) = str_to_vec(&line);
-
-        // create regex to match possible arrangements
-        let re = build_regex(&groups);
+        if groups.iter().any(|&g| g < 1) {
+            bail!("groups must be at least 1 long in '{line}'");
+        }
 
         // count matches
-        let mut matches = Vec::new();
-        match_groups(0, &springs, &groups, &mut matches);
-        //let matches:Vec<_> = re.find_iter(&springs).map(|m|m.as_str()).collect();
-        if DEBUG { eprintln!("matches: {matches:?}") };
-        let steps = matches.len();
+        let steps = config.backend.count(&springs, &groups)?;
         if DEBUG { eprintln!("-> {steps:?}") };
-        result += steps;
-    }
+        result = add_count(result, steps)?;
+
+        if let Some(page) = &config.show {
+            println!("{line}");
+            for arrangement in Ways::new(&springs, &groups)?.arrangements(page.clone()) {
+                println!("{}", render(&springs, &arrangement));
+            }
+        }
 
+        if config.hints {
+            match hints(&springs, &groups)? {
+                Some(hint) => {
+                    let ambiguous = hint.bytes().filter(|&c| c == b'?').count();
+                    let forced = springs.bytes().filter(|&c| c == b'?').count() - ambiguous;
+                    println!("{}  {forced} forced, {ambiguous} ambiguous, {steps} arrangements", render(&springs, &hint));
+                },
+                None => println!("{springs}  no arrangements"),
+            }
+        }
 
-    // PART TWO
-    //eprintln!("PART TWO");
+        let (springs, groups) = unfold(&springs, &groups, config.unfold);
+        unfolded_result = add_count(unfolded_result, config.backend.count(&springs, &groups)?)?;
+    }
 
     // output the result
     println!("{result}");
 
-    return Ok(());
+    // PART TWO
+    eprintln!("PART TWO");
+
+    println!("{unfolded_result}");
+
+    return Ok((result, unfolded_result));
 }
 
 fn main() -> Result<(),Error> {
-    go(&mut std::io::stdin().lock())
+    // --unfold N: how many copies of each row part 2 joins up, five by default
+    // --show K N: arrangements K to K+N of each row, under the row with the '?' cells highlighted
+    // --backend table|nfa|both: which counter to use, both checks one against the other
+    // --hints: each row with the '?' cells every arrangement agrees on filled in
+    let mut config = Config::default();
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        let mut value = || args.next().with_context(|| format!("missing value for {arg}"));
+        match arg.as_str() {
+            "--unfold" => config.unfold = value()?.parse()?,
+            "--backend" => config.backend = Backend::parse(&value()?)?,
+            "--hints" => config.hints = true,
+            "--show" => {
+                let (first, count): (u64, u64) = (value()?.parse()?, value()?.parse()?);
+                config.show = Some(first..first.saturating_add(count));
+            },
+            _ => bail!("unknown argument {arg}"),
+        }
+    }
+    go(&mut std::io::stdin().lock(), &config)?;
+    Ok(())
 }
 
 #[derive(PartialEq)]
@@ -133,7 +344,11 @@ r"???.### 1,1,3
 ????.######..#####. 1,6,5
 ?###???????? 3,2,1";  
 
-    go(&mut testinput.as_bytes())
+    assert_eq!(go(&mut testinput.as_bytes(), &Config::default())?, (21, 525152));
+    assert_eq!(go(&mut testinput.as_bytes(), &Config { unfold: 1, show: Some(0..3), backend: Backend::Table, hints: true })?, (21, 21));
+    assert_eq!(go(&mut testinput.as_bytes(), &Config { backend: Backend::Nfa, ..Config::default() })?, (21, 525152));
+    assert_eq!(go(&mut testinput.as_bytes(), &Config { backend: Backend::Both, ..Config::default() })?, (21, 525152));
+    Ok(())
 }
 
 #[test]
@@ -151,3 +366,118 @@ r"#.#.### 1,1,3
     assert_eq!(retv[0], (String::from("#.#.###"), vec![1,1,3]));
 }
 
+
+#[test]
+fn test_count() {
+    let testinput = 
+r"???.### 1,1,3
+.??..??...?##. 1,1,3
+?#?#?#?#?#?#?#? 1,3,1,6
+????.#...#... 4,1,1
+????.######..#####. 1,6,5
+?###???????? 3,2,1";
+
+    let rows:Vec<_> = testinput.lines().map(str_to_vec).collect();
+    let counts:Vec<u64> = rows.iter().map(|(springs, groups)| count_arrangements(springs, groups).unwrap()).collect();
+    assert_eq!(counts, [1, 4, 1, 1, 4, 10]);
+    let unfolded:Vec<u64> = rows.iter().map(|(springs, groups)| {
+        let (springs, groups) = unfold(springs, groups, 5);
+        count_arrangements(&springs, &groups).unwrap()
+    }).collect();
+    assert_eq!(unfolded, [1, 16384, 1, 16, 2500, 506250]);
+
+    assert_eq!(unfold("#.", &[1], 3), (String::from("#.?#.?#."), vec![1, 1, 1]));
+    // no groups fit only when nothing has to be damaged
+    assert_eq!(count_arrangements("??.", &[]).unwrap(), 1);
+    assert_eq!(count_arrangements("?#.", &[]).unwrap(), 0);
+    assert_eq!(count_arrangements("", &[1]).unwrap(), 0);
+    assert_eq!(count_arrangements("###", &[3]).unwrap(), 1);
+    assert_eq!(count_arrangements("###", &[2]).unwrap(), 0);
+
+    // too many arrangements for a u64 is an error from either counter, not a wrapped count
+    let (springs, groups) = unfold("??????????", &[1], 40);
+    assert!(count_arrangements(&springs, &groups).is_err());
+    assert!(nfa::count_nfa(&springs, &groups).is_err());
+    for backend in [Backend::Table, Backend::Nfa] {
+        let config = Config { unfold: 40, backend, ..Config::default() };
+        assert!(go(&mut "?????????? 1".as_bytes(), &config).is_err());
+    }
+}
+
+#[test]
+fn test_arrangements() {
+    let testinput = 
+r"???.### 1,1,3
+.??..??...?##. 1,1,3
+?#?#?#?#?#?#?#? 1,3,1,6
+????.#...#... 4,1,1
+????.######..#####. 1,6,5
+?###???????? 3,2,1";
+
+    for (springs, groups) in testinput.lines().map(str_to_vec) {
+        let ways = Ways::new(&springs, &groups).unwrap();
+        let all: Vec<String> = ways.arrangements(0..u64::MAX).collect();
+        assert_eq!(all.len() as u64, ways.count());
+        for arrangement in all.iter() {
+            assert_eq!(oracle::groups_of(arrangement.as_bytes()), groups, "{arrangement}");
+            assert!(springs.chars().zip(arrangement.chars()).all(|(was, now)| was == '?' || was == now), "{arrangement}");
+        }
+        let mut distinct = all.clone();
+        distinct.sort();
+        distinct.dedup();
+        assert_eq!(distinct.len(), all.len());
+
+        // pages are slices of the whole list
+        assert_eq!(ways.arrangements(2..5).collect::<Vec<_>>(), all[2.min(all.len())..5.min(all.len())]);
+        assert_eq!(ways.nth(ways.count()), None);
+    }
+
+    // working springs come first
+    let ways = Ways::new("???.###", &[1, 1, 3]).unwrap();
+    assert_eq!(ways.nth(0).as_deref(), Some("#.#.###"));
+    let ways = Ways::new(".??..??...?##.", &[1, 1, 3]).unwrap();
+    assert_eq!(ways.arrangements(0..2).collect::<Vec<_>>(), ["..#...#...###.", "..#..#....###."]);
+
+    assert_eq!(render("?#?", "##."), format!("{HIGHLIGHT}#{PLAIN}#{HIGHLIGHT}.{PLAIN}"));
+    assert_eq!(render("??", ".?"), format!("{HIGHLIGHT}.{PLAIN}?"));
+}
+
+#[test]
+fn test_hints() {
+    assert_eq!(hints("???.###", &[1, 1, 3]).unwrap().as_deref(), Some("#.#.###"));
+    assert_eq!(hints(".??..??...?##.", &[1, 1, 3]).unwrap().as_deref(), Some(".??..??...###."));
+    assert_eq!(hints("?###????????", &[3, 2, 1]).unwrap().as_deref(), Some(".###.???????"));
+    // a nonogram classic: a 3 in 4 cells always covers the middle two
+    assert_eq!(hints("????", &[3]).unwrap().as_deref(), Some("?##?"));
+    assert_eq!(hints("#?#", &[1]).unwrap(), None);
+
+    // forced cells are exactly the ones every arrangement agrees on
+    let mut random = oracle::lcg(50);
+    for _ in 0..500 {
+        let (springs, groups) = oracle::random_row(&mut random, 12);
+        let all: Vec<String> = Ways::new(&springs, &groups).unwrap().arrangements(0..u64::MAX).collect();
+        let Some(hint) = hints(&springs, &groups).unwrap() else {
+            assert!(all.is_empty(), "{springs} {groups:?}");
+            continue;
+        };
+        for (i, c) in hint.bytes().enumerate() {
+            let agreed = all.iter().all(|a| a.as_bytes()[i] == all[0].as_bytes()[i]);
+            assert_eq!(c != b'?', agreed, "{springs} {groups:?} -> {hint}");
+            if agreed {
+                assert_eq!(c, all[0].as_bytes()[i], "{springs} {groups:?} -> {hint}");
+            }
+        }
+
+        // and on rows too long to list: a cell is forced when the other value leaves no arrangements
+        let (springs, groups) = unfold(&springs, &groups, 5);
+        let hint = hints(&springs, &groups).unwrap().unwrap();
+        for (i, c) in hint.bytes().enumerate().filter(|&(i, _)| springs.as_bytes()[i] == b'?') {
+            let count_with = |value:&str| count_arrangements(&format!("{}{value}{}", &springs[..i], &springs[i+1..]), &groups).unwrap();
+            assert_eq!(c, match (count_with("#") > 0, count_with(".") > 0) {
+                (true, false) => b'#',
+                (false, true) => b'.',
+                _ => b'?',
+            }, "{springs} {groups:?} -> {hint}");
+        }
+    }
+}
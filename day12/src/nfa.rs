@@ -0,0 +1,70 @@
+// the second opinion: the row's pattern as a little automaton, counting paths through it.
+// for groups 1,1,3 the pattern is ".#.#.###." where each '.' state can also stay put on a '.'.
+// a known spring takes each path down its one edge, a '?' takes both, and each arrangement
+// is exactly one path, so the count in the accepting states is the number of arrangements
+
+use anyhow::{Context, Error};
+
+pub fn pattern(groups:&[i32]) -> Vec<u8> {
+    let mut pattern = vec![b'.'];
+    for &g in groups {
+        pattern.extend(std::iter::repeat_n(b'#', g as usize));
+        pattern.push(b'.');
+    }
+    pattern
+}
+
+pub fn count_nfa(springs:&str, groups:&[i32]) -> Result<u64,Error> {
+    let pattern = pattern(groups);
+    let last = pattern.len() - 1;
+    let mut paths = vec![0u64; pattern.len()];
+    paths[0] = 1;
+    let mut next = vec![0u64; pattern.len()];
+    let overflow = || format!("more than {} arrangements of {springs}", u64::MAX);
+    for c in springs.bytes() {
+        next.fill(0);
+        for (state, &count) in paths.iter().enumerate().filter(|(_, &count)| count > 0) {
+            for spring in [b'#', b'.'] {
+                if c != b'?' && c != spring {
+                    continue;
+                }
+                if state < last && pattern[state+1] == spring {
+                    next[state+1] = next[state+1].checked_add(count).with_context(overflow)?;
+                }
+                if pattern[state] == b'.' && spring == b'.' {
+                    next[state] = next[state].checked_add(count).with_context(overflow)?;
+                }
+            }
+        }
+        std::mem::swap(&mut paths, &mut next);
+    }
+    // done in the final '.', or straight after the last group's final '#'
+    paths[last].checked_add(if last > 0 { paths[last-1] } else { 0 }).with_context(overflow)
+}
+
+#[test]
+fn test_nfa() {
+    assert_eq!(pattern(&[1, 1, 3]), b".#.#.###.");
+    assert_eq!(count_nfa("???.###", &[1, 1, 3]).unwrap(), 1);
+    assert_eq!(count_nfa("?###????????", &[3, 2, 1]).unwrap(), 10);
+    assert_eq!(count_nfa("??.", &[]).unwrap(), 1);
+    assert_eq!(count_nfa("?#.", &[]).unwrap(), 0);
+    assert_eq!(count_nfa("", &[]).unwrap(), 1);
+    assert_eq!(count_nfa("", &[1]).unwrap(), 0);
+}
+
+#[test]
+fn test_nfa_against_table() {
+    use crate::oracle::{count_brute_force, lcg, random_row};
+    let mut random = lcg(49);
+    for _ in 0..1000 {
+        let (springs, groups) = random_row(&mut random, 12);
+        let expected = count_brute_force(&springs, &groups);
+        assert_eq!(count_nfa(&springs, &groups).unwrap(), expected, "{springs} {groups:?}");
+        assert_eq!(crate::count_arrangements(&springs, &groups).unwrap(), expected, "{springs} {groups:?}");
+
+        // and on rows too long for the oracle
+        let (springs, groups) = crate::unfold(&springs, &groups, 5);
+        assert_eq!(count_nfa(&springs, &groups).unwrap(), crate::count_arrangements(&springs, &groups).unwrap(), "{springs} {groups:?}");
+    }
+}
@@ -0,0 +1,78 @@
+// the slow, obviously right answer, and random rows to hold the fast counters to it
+
+use crate::{count_arrangements, unfold};
+
+// the damaged group lengths of a fully known row, "#.##..#" is 1,2,1
+pub fn groups_of(springs:&[u8]) -> Vec<i32> {
+    springs.split(|&c| c != b'#').filter(|run| !run.is_empty()).map(|run| run.len() as i32).collect()
+}
+
+// try all 2^k ways to fill in the k unknown springs, and keep the ones with the right groups
+pub fn count_brute_force(springs:&str, groups:&[i32]) -> u64 {
+    let unknown: Vec<usize> = springs.bytes().enumerate().filter_map(|(i, c)| (c == b'?').then_some(i)).collect();
+    assert!(unknown.len() < 32, "{} unknown springs is too many to try them all", unknown.len());
+    let mut row = springs.as_bytes().to_vec();
+    let mut count = 0;
+    for mask in 0u64..1 << unknown.len() {
+        for (bit, &i) in unknown.iter().enumerate() {
+            row[i] = if mask >> bit & 1 == 1 { b'#' } else { b'.' };
+        }
+        if groups_of(&row) == groups {
+            count += 1;
+        }
+    }
+    count
+}
+
+// seeded, so a failing row comes back on every run. random(n) is in 0..n
+pub fn lcg(mut seed:u64) -> impl FnMut(usize) -> usize {
+    move |n| {
+        seed = seed.wrapping_mul(6364136223846793005).wrapping_add(1442695040888963407);
+        ((seed >> 33) % n as u64) as usize
+    }
+}
+
+// a random row of up to `max_len` springs with some of them hidden behind '?'.
+// mostly the groups come from the row before hiding, so there's at least one arrangement,
+// and sometimes they're made up, so there often isn't
+pub fn random_row(random:&mut impl FnMut(usize) -> usize, max_len:usize) -> (String, Vec<i32>) {
+    let len = random(max_len + 1);
+    let known: Vec<u8> = (0..len).map(|_| if random(2) == 0 { b'#' } else { b'.' }).collect();
+    let groups = if random(4) == 0 {
+        (0..random(4)).map(|_| random(4) as i32 + 1).collect()
+    } else {
+        groups_of(&known)
+    };
+    let hide = random(4) + 1;
+    let springs = known.iter().map(|&c| if random(4) < hide { '?' } else { c as char }).collect();
+    (springs, groups)
+}
+
+#[test]
+fn test_oracle() {
+    assert_eq!(groups_of(b"#.##..#"), [1, 2, 1]);
+    assert_eq!(groups_of(b"..."), []);
+    assert_eq!(count_brute_force("???.###", &[1, 1, 3]), 1);
+    assert_eq!(count_brute_force("?###????????", &[3, 2, 1]), 10);
+    assert_eq!(count_brute_force("", &[]), 1);
+}
+
+#[test]
+fn test_count_against_oracle() {
+    let mut random = lcg(12);
+    for _ in 0..2000 {
+        let (springs, groups) = random_row(&mut random, 12);
+        assert_eq!(count_arrangements(&springs, &groups).unwrap(), count_brute_force(&springs, &groups), "{springs} {groups:?}");
+    }
+}
+
+#[test]
+fn test_unfolded_against_oracle() {
+    let mut random = lcg(5);
+    for _ in 0..300 {
+        let (springs, groups) = random_row(&mut random, 4);
+        let times = random(3) + 1;
+        let (springs, groups) = unfold(&springs, &groups, times);
+        assert_eq!(count_arrangements(&springs, &groups).unwrap(), count_brute_force(&springs, &groups), "{springs} {groups:?}");
+    }
+}
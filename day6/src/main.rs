@@ -1,6 +1,9 @@
+use std::fmt::Display;
 use std::io::{Lines, BufRead, BufReader};
+use std::ops::RangeInclusive;
 
 use anyhow::{Context,Error};
+use num_bigint::BigUint;
 
 const DEBUG:bool = cfg!(debug_assertions);
 
@@ -8,14 +11,15 @@ fn str_to_vec(somestr: &str, skip:usize) -> Vec<u32> {
     somestr.split_ascii_whitespace().skip(skip).map(|s| u32::from_str_radix(s, 10).expect("string to be sequence of u32")).collect()
 }
 
-fn str_to_joined(somestr: &str, skip:usize) -> u64 {
+fn str_to_joined(somestr: &str, skip:usize) -> BigUint {
     let s:String = somestr.split_ascii_whitespace().skip(skip).collect();
     if DEBUG { eprintln!("'{}' => {}", somestr, &s) };   
-    s.parse().expect("u64 string")
+    s.parse().expect("string of digits")
 }
 
 const ACCEL:u32 = 1; // 1 ms-per-ms
 
+#[cfg(test)]
 fn distance(hold_time: u32, race_time: u32) -> u64 {
     if hold_time >= race_time {
         return 0;
@@ -24,6 +28,8 @@ fn distance(hold_time: u32, race_time: u32) -> u64 {
     return (race_time-hold_time) as u64 *(hold_time as u64);
 }
 
+// every hold time tried in turn, the reference for the closed forms
+#[cfg(test)]
 fn ways_to_win(racetime: u32, record: u64) -> u32 {
     let mut ways_to_win = 0;
     for t in 1..racetime {
@@ -35,7 +41,92 @@ fn ways_to_win(racetime: u32, record: u64) -> u32 {
     return ways_to_win;
 }
 
-fn go(input:&mut dyn BufRead) -> Result<(),Error>{
+// winning hold times h satisfy h*(t-h) > d, so they lie strictly between the roots of h^2 - t*h + d = 0.
+// the integer square root puts the first guess within one of the lower root, and the wins are symmetric about t/2.
+// returns None if the arithmetic would overflow a u64
+fn ways_to_win_checked(racetime: u64, record: u64) -> Option<u64> {
+    let Some(disc) = racetime.checked_mul(racetime)?.checked_sub(record.checked_mul(4)?) else {
+        // no real roots, the record can't be beaten
+        return Some(0);
+    };
+
+    let mut lo = (racetime - disc.isqrt()) / 2;
+    if lo * (racetime - lo) <= record {
+        lo += 1;
+    }
+    if 2 * lo > racetime {
+        return Some(0);
+    }
+    return Some(racetime - 2 * lo + 1);
+}
+
+// same closed form as ways_to_win_checked, for races too long for a u64
+fn ways_to_win_big(racetime: &BigUint, record: &BigUint) -> BigUint {
+    let square = racetime * racetime;
+    let four_record = record * 4u32;
+    if square < four_record {
+        return BigUint::ZERO;
+    }
+    let disc = square - four_record;
+
+    let mut lo = (racetime - disc.sqrt()) / 2u32;
+    if &lo * (racetime - &lo) <= *record {
+        lo += 1u32;
+    }
+    let twice_lo = &lo * 2u32;
+    if &twice_lo > racetime {
+        return BigUint::ZERO;
+    }
+    return racetime - twice_lo + 1u32;
+}
+
+// use the u64 path when everything fits, fall back to BigUint when it would overflow
+fn ways_to_win_exact(racetime: &BigUint, record: &BigUint) -> BigUint {
+    if let (Ok(t), Ok(d)) = (u64::try_from(racetime), u64::try_from(record)) {
+        if let Some(ways) = ways_to_win_checked(t, d) {
+            return BigUint::from(ways);
+        }
+        if DEBUG { eprintln!("u64 overflow for race {t}, record {d}") };
+    }
+    return ways_to_win_big(racetime, record);
+}
+
+// one row of the race-by-race report
+#[derive(Debug)]
+struct RaceSummary {
+    time: BigUint,
+    record: BigUint,
+    holds: Option<RangeInclusive<BigUint>>,
+    ways: BigUint,
+    best: BigUint,
+}
+
+impl RaceSummary {
+    fn new(time: BigUint, record: BigUint) -> Self {
+        let ways = ways_to_win_exact(&time, &record);
+        // wins are symmetric about time/2, so the interval falls out of the count
+        let holds = (ways > BigUint::ZERO).then(|| {
+            let lo = (&time + 1u32 - &ways) / 2u32;
+            let hi = &time - &lo;
+            lo..=hi
+        });
+        let half = &time / 2u32;
+        let best = &half * (&time - &half);
+        Self { time, record, holds, ways, best }
+    }
+}
+
+impl Display for RaceSummary {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let holds = match &self.holds {
+            Some(r) => format!("{}..={}", r.start(), r.end()),
+            None => String::from("-"),
+        };
+        write!(f, "{:>16} {:>20} {:>24} {:>16} {:>20}", self.time, self.record, holds, self.ways, self.best)
+    }
+}
+
+fn go(input:&mut dyn BufRead, report: bool) -> Result<(BigUint,BigUint),Error>{
     // boat racing
     //Time:      7  15  30
     //Distance:  9  40 200
@@ -54,27 +145,47 @@ fn go(input:&mut dyn BufRead) -> Result<(),Error>{
     if DEBUG { eprintln!("times: {:?}", &times_str) };
 
     
-    let mut margin_error = 1;
-    for race in 0..times.len() {
-        margin_error *= ways_to_win(times[race], records[race] as u64);
-    }
+    // closed form for each race, multiplied in u64 while that fits, and all in BigUint once it doesn't
+    let margin_error = times.iter().zip(&records)
+        .try_fold(1u64, |product, (&time, &record)| product.checked_mul(ways_to_win_checked(time as u64, record as u64)?))
+        .map(BigUint::from)
+        .unwrap_or_else(|| {
+            if DEBUG { eprintln!("u64 overflow multiplying the races") };
+            times.iter().zip(&records).map(|(&time, &record)| ways_to_win_exact(&time.into(), &record.into())).product()
+        });
     
     if DEBUG { eprintln!("margin_error: {:?}", &margin_error) };
 
-    eprintln!("PART TWO");
-
-    let times = str_to_joined(&times_str, 1) as u32;
+    let time = str_to_joined(&times_str, 1);
     let record = str_to_joined(&dist_str, 1);
 
-    let mut margin_error = ways_to_win(times, record);
+    if report {
+        println!("{:>16} {:>20} {:>24} {:>16} {:>20}", "time", "record", "winning holds", "ways", "best");
+        for race in 0..times.len() {
+            println!("{}", RaceSummary::new(times[race].into(), records[race].into()));
+        }
+        println!("{}", RaceSummary::new(time.clone(), record.clone()));
+    }
+
+    // output the result
+    println!("{margin_error}");
+
+    eprintln!("PART TWO");
+
+    let joined_margin_error = ways_to_win_exact(&time, &record);
     
-    if DEBUG { eprintln!("margin_error: {:?}", &margin_error) };
+    if DEBUG { eprintln!("margin_error: {:?}", &joined_margin_error) };
+
+    println!("{joined_margin_error}");
 
-    return Ok(());
+    return Ok((margin_error, joined_margin_error));
 }
 
 fn main() -> Result<(),Error> {
-    go(&mut std::io::stdin().lock())
+    // --report prints a table of every race, then the joined race, ahead of the answers
+    let report = std::env::args().skip(1).any(|arg| arg == "--report");
+    go(&mut std::io::stdin().lock(), report)?;
+    Ok(())
 }
 
 #[test]
@@ -83,7 +194,22 @@ fn example() -> Result<(),Error> {
 r"Time:      7  15   30
 Distance:  9  40  200";
 
-    go(&mut testinput.as_bytes())
+    let (part1, part2) = go(&mut testinput.as_bytes(), true)?;
+    assert_eq!(part1, BigUint::from(288u32));
+    assert_eq!(part2, BigUint::from(71503u32));
+    Ok(())
+}
+
+#[test]
+fn test_summary() {
+    let summary = RaceSummary::new(BigUint::from(30u32), BigUint::from(200u32));
+    assert_eq!(summary.holds, Some(BigUint::from(11u32)..=BigUint::from(19u32)));
+    assert_eq!(summary.ways, BigUint::from(9u32));
+    assert_eq!(summary.best, BigUint::from(225u32));
+
+    let summary = RaceSummary::new(BigUint::from(4u32), BigUint::from(4u32));
+    assert_eq!(summary.holds, None);
+    assert_eq!(summary.best, BigUint::from(4u32));
 }
 
 #[test]
@@ -102,3 +228,40 @@ fn test() {
     assert_eq!(distance(6, 7), 6);
 }
 
+
+#[test]
+fn test_closed_form() {
+    for racetime in 0..60u32 {
+        for record in 0..1000u64 {
+            assert_eq!(ways_to_win_checked(racetime as u64, record), Some(ways_to_win(racetime, record) as u64), "race {racetime}, record {record}");
+            assert_eq!(ways_to_win_big(&BigUint::from(racetime), &BigUint::from(record)), BigUint::from(ways_to_win(racetime, record)));
+        }
+    }
+    assert_eq!(ways_to_win_checked(71530, 940200), Some(71503));
+}
+
+#[test]
+fn test_overflow() {
+    // joined strings longer than a u64 parse exactly
+    let time = str_to_joined("Time: 40000000000 00000000000", 1);
+    let record = str_to_joined("Distance: 1 000000000000000000000000000000000000000", 1);
+    assert_eq!(time.to_string(), "4000000000000000000000");
+    assert_eq!(ways_to_win_checked(u64::MAX, 1), None);
+
+    // 4e21 ms race against a 1e39 mm record:
+    // wins lie strictly between the roots 2e21 +- sqrt(4e42 - 4e39)/2
+    let ways = ways_to_win_exact(&time, &record);
+    let lo = (&time - &ways + 1u32) / 2u32;
+    assert!(&lo * (&time - &lo) > record);
+    let before = &lo - 1u32;
+    assert!(&before * (&time - &before) <= record);
+}
+
+#[test]
+fn test_product_overflow() -> Result<(),Error> {
+    // each race fits a u64 but three of them multiplied don't
+    let testinput = "Time: 4000000000 4000000000 4000000000\nDistance: 0 0 0\n";
+    let (part1, _) = go(&mut testinput.as_bytes(), false)?;
+    assert_eq!(part1, BigUint::from(3999999999u64).pow(3));
+    Ok(())
+}
@@ -1,16 +1,58 @@
-use std::collections::{HashMap};
+use std::borrow::Cow;
 use std::io::{BufRead, BufReader};
 
-use anyhow::{Error};
+use anyhow::{bail, Context, Error};
+
+mod sim;
 
 const DEBUG:bool = cfg!(debug_assertions);
 
-fn cardvalue(card: char) -> u32 {
-    // reorder for joker
-    "J23456789TQKA".find(card).expect("bad card value") as u32 + 2
+// which cards exist, how they rank, and how a wildcard behaves
+#[derive(Debug)]
+struct Rules {
+    // card alphabet, weakest to strongest
+    order: Cow<'static, str>,
+    wildcard: Option<char>,
+    // whether the wildcard joins the most common card when typing the hand
+    wild_type: bool,
+    // hand shapes, weakest to strongest. None ranks shapes lexicographically,
+    // which is the usual poker order: [2,2,1] beats [2,1,1,1], [5] beats [4,1]
+    shapes: Option<Vec<Vec<u32>>>,
+}
+
+impl Rules {
+    const STANDARD: Rules = Rules { order: Cow::Borrowed("23456789TJQKA"), wildcard: None, wild_type: false, shapes: None };
+    // J is a joker: weakest card on ties, but counts as whatever makes the best hand
+    const JOKERS: Rules = Rules { order: Cow::Borrowed("J23456789TQKA"), wildcard: Some('J'), wild_type: true, shapes: None };
+
+    // bits to hold any card value
+    fn card_bits(&self) -> u32 {
+        u32::BITS - (self.order.chars().count() as u32 + 1).leading_zeros()
+    }
+
+    // position of a shape in the ranking, for the high half of the sort key
+    fn shape_rank(&self, shape: &[u32]) -> Result<u64,Error> {
+        match &self.shapes {
+            Some(shapes) => Ok(shapes.iter().position(|s| s == shape).with_context(|| format!("unranked hand shape {shape:?}"))? as u64),
+            None => {
+                // counts are at most the hand size, so pack them as digits in base size+1
+                let size = shape.iter().sum::<u32>() as u64;
+                let mut rank = 0u64;
+                for i in 0..size as usize {
+                    let count = shape.get(i).copied().unwrap_or(0) as u64;
+                    rank = rank.checked_mul(size + 1).and_then(|r| r.checked_add(count)).context("hand too large to rank")?;
+                }
+                Ok(rank)
+            },
+        }
+    }
 }
 
-#[derive(Debug,PartialOrd,PartialEq)]
+fn cardvalue(card: char, rules: &Rules) -> Result<u32,Error> {
+    Ok(rules.order.chars().position(|c| c == card).with_context(|| format!("bad card value '{card}'"))? as u32 + 2)
+}
+
+#[derive(Debug,Clone,Copy,PartialOrd,Ord,PartialEq,Eq)]
 enum HandType {
     HighCard,//(u32),
     OnePair,
@@ -21,109 +63,198 @@ enum HandType {
     FiveKind,
 }
 
-#[derive(Debug)]
-struct Hand(String);
-impl Hand {
-    fn count_cards(&self) -> HandType {
-        let mut counts = HashMap::<char, u32>::new();
-        for card in self.0.chars() {
-            counts.entry(card).and_modify(|c| *c += 1).or_insert(1);
-        }
+impl HandType {
+    const ALL: [HandType; 7] = [HandType::HighCard, HandType::OnePair, HandType::TwoPair, HandType::ThreeKind, HandType::FullHouse, HandType::FourKind, HandType::FiveKind];
 
-        // get the joker
-        if let Some(&jokers) = counts.get(&'J') {
-            if jokers != 5 {
-                counts.remove(&'J');
-                let maxentry = counts.iter_mut().max_by(|l,r| l.1.cmp(&r.1)).unwrap();
-                *maxentry.1 += jokers;
-            }
+    // the five card names for hand shapes
+    fn from_shape(shape: &[u32]) -> Option<HandType> {
+        match shape {
+            [1,1,1,1,1] => Some(HandType::HighCard),
+            [2,1,1,1] => Some(HandType::OnePair),
+            [2,2,1] => Some(HandType::TwoPair),
+            [3,1,1] => Some(HandType::ThreeKind),
+            [3,2] => Some(HandType::FullHouse),
+            [4,1] => Some(HandType::FourKind),
+            [5] => Some(HandType::FiveKind),
+            _ => None,
         }
+    }
+}
 
-        match counts.len() {
-            5 => HandType::HighCard,
-            4 => HandType::OnePair,
-            3 => {
-                for (_,count) in counts.iter() {
-                    if *count == 2 {
-                        return HandType::TwoPair;
-                    }
-                }
-                HandType::ThreeKind
-            }
-            2 => { 
-                match counts.iter().nth(0).unwrap().1 {
-                    4 | 1 => HandType::FourKind,
-                    _ => HandType::FullHouse,
-                }
-            },
-            1 => HandType::FiveKind,
-            _ => panic!("couldn't count cards in {:?}", self),
+// card counts, largest first, with any wildcards joining the largest
+fn hand_shape(cards: &str, rules: &Rules) -> Vec<u32> {
+    // sorted, each card's copies sit together
+    let mut sorted: Vec<char> = cards.chars().collect();
+    sorted.sort_unstable();
+
+    let mut shape = Vec::new();
+    let mut jokers = 0;
+    for run in sorted.chunk_by(|a, b| a == b) {
+        if rules.wild_type && rules.wildcard == Some(run[0]) {
+            jokers = run.len() as u32;
+        } else {
+            shape.push(run.len() as u32);
         }
     }
+    shape.sort_unstable_by(|l,r| r.cmp(l));
+    match shape.first_mut() {
+        Some(most) => *most += jokers,
+        None => shape.push(jokers),
+    }
+    shape
 }
 
-impl PartialEq for Hand {
-    fn eq(&self, _other: &Self) -> bool {
-        panic!("Shouldn't compare hands directly");
-    }
+// a hand, keyed for sorting under one set of rules.
+// the key is the shape rank in the high 64 bits, then each card value packed in order below it,
+// so comparing keys compares shape first and then card-by-card. cards is only a tie breaker for identical keys,
+// and the shape is kept from keying so nothing counts the cards again
+#[derive(Debug,PartialEq,Eq,PartialOrd,Ord)]
+struct Hand {
+    key: u128,
+    cards: String,
+    shape: Vec<u32>,
 }
-impl PartialOrd for Hand {
-    fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
-        let left = self.count_cards();
-        let right = other.count_cards();
-        if left == right {
-            // actually.... HighCard doesn't win on the value of the HighCard.
-            // if let (HandType::HighCard(lvalue),HandType::HighCard(rvalue)) = (left,right) {
-            //     if lvalue != rvalue {
-            //         return lvalue.partial_cmp(&rvalue);
-            //     }
-            // }
-
-            // all else equal, compare card values in order
-            return Some(self.0.chars().map(cardvalue).cmp(other.0.chars().map(cardvalue)));
-        } else {
-            return left.partial_cmp(&right);
+
+impl Hand {
+    // hands too big for the key are an error, hand_shape still classifies them
+    fn new(cards: &str, rules: &Rules) -> Result<Self,Error> {
+        let bits = rules.card_bits();
+        if cards.chars().count() as u32 * bits > 64 {
+            bail!("too many cards to key {cards}");
+        }
+        let mut packed = 0u64;
+        for card in cards.chars() {
+            packed = packed << bits | cardvalue(card, rules)? as u64;
         }
+        let shape = hand_shape(cards, rules);
+        let key = (rules.shape_rank(&shape)? as u128) << 64 | packed as u128;
+        Ok(Hand { key, cards: cards.to_owned(), shape })
+    }
+
+    // card counts under the rules the hand was keyed with
+    fn shape(&self) -> &[u32] {
+        &self.shape
+    }
+
+    // the five card type, for checking keys against
+    #[cfg(test)]
+    fn count_cards(&self) -> HandType {
+        HandType::from_shape(&self.shape).unwrap_or_else(|| panic!("couldn't count cards in {:?}", self))
     }
 }
 
-fn go(input:&mut dyn BufRead) -> Result<(),Error>{
-    // camel cards poker
-    // puzzle input, list of hands
-    // output is ordered list of hands by strength
+// rank the hands under the given rules, and total up bid * rank
+fn winnings(hands: &[(String,u32)], rules: &Rules) -> Result<u64,Error> {
+    let mut ranked: Vec<(Hand,u32)> = hands.iter().map(|(cards,bid)| Ok((Hand::new(cards, rules)?, *bid))).collect::<Result<_,Error>>()?;
+    ranked.sort_unstable();
+    if DEBUG { eprintln!("hands: {:?}", &ranked) };
+    
+    let mut winnings = 0;
+    for (i,(hand,bid)) in ranked.iter().enumerate() {
+        winnings += (i+1) as u64 * *bid as u64;
+        if DEBUG { eprintln!("{i} {}{:?} => {}", hand.cards, hand.shape(), (i+1)as u32*bid) };
+    }
+    return Ok(winnings);
+}
 
+fn read_hands(input:&mut dyn BufRead) -> Result<Vec<(String,u32)>,Error> {
     let mut lines = BufReader::new(input).lines();
     let mut hands = Vec::new();
     while let Some(Ok(line)) = lines.next() {
         //32T3K 765
-        let (hand,bid_str) = line.split_once(' ').unwrap();
-        let bid = bid_str.parse::<u32>().unwrap();
-        hands.push((Hand(hand.to_owned()),bid));
+        let (hand,bid_str) = line.split_once(' ').with_context(|| format!("missing bid in '{line}'"))?;
+        let bid = bid_str.parse::<u32>()?;
+        hands.push((hand.to_owned(),bid));
     }
+    Ok(hands)
+}
 
-    hands.sort_by(|(left,_),(right,_)| {
-        let ord = left.partial_cmp(&right).unwrap();
-        if DEBUG { eprintln!("{left:?} {:?} {right:?}", ord) };
-        ord
-    });
-    if DEBUG { eprintln!("hands: {:?}", &hands) };
-    
-    let mut winnings = 0;
-    for (i,(hand,bid)) in hands.iter().enumerate() {
-        winnings += (i+1) as u32 *bid;
-        if DEBUG { eprintln!("{i} {:?}{:?} => {}", hand, hand.count_cards(), (i+1)as u32*bid) };
-    }
+fn go(input:&mut dyn BufRead) -> Result<(u64,u64),Error>{
+    // camel cards poker
+    // puzzle input, list of hands
+    // output is ordered list of hands by strength
 
-    println!("{winnings}");
+    let hands = read_hands(input)?;
 
-    // PART TWO. The seed numbers are ranges, with the start and length in each pair
+    let standard = winnings(&hands, &Rules::STANDARD)?;
+
+    println!("{standard}");
+
+    // PART TWO. J cards are now jokers
     eprintln!("PART TWO");
 
-    return Ok(());
+    let jokers = winnings(&hands, &Rules::JOKERS)?;
+
+    println!("{jokers}");
+
+    return Ok((standard, jokers));
 }
 
 fn main() -> Result<(),Error> {
-    go(&mut std::io::stdin().lock())
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    match args.first().map(String::as_str) {
+        // hand type probabilities, with and without jokers.
+        // --odds SIZE SAMPLES [SEED] estimates hand shape odds for other hand sizes instead
+        Some("--odds") => {
+            let size = args.get(1).map(|s| s.parse::<usize>()).transpose()?;
+            for (name, rules) in [("standard", Rules::STANDARD), ("jokers", Rules::JOKERS)] {
+                println!("{name}");
+                match size {
+                    Some(size) => {
+                        let samples = args.get(2).context("missing sample count")?.parse()?;
+                        let mut rng = sim::Rng::new(args.get(3).map_or(Ok(2023), |s| s.parse())?);
+                        for (shape, p) in sim::sample_shape_odds(&mut rng, &rules, size, samples) {
+                            println!("  {shape:?}: {p:.6}");
+                        }
+                    },
+                    None => {
+                        for (hand_type, p) in sim::hand_type_odds(&rules) {
+                            println!("  {hand_type:?}: {p:.6}");
+                        }
+                    },
+                }
+            }
+        },
+        // --tournament HAND BID OPPONENTS [ROUNDS] [SEED]
+        Some("--tournament") => {
+            let cards = args.get(1).context("missing hand")?;
+            if let Some(card) = cards.chars().find(|&c| !Rules::STANDARD.order.contains(c)) {
+                bail!("'{card}' in {cards} isn't a card, expected one of {}", Rules::STANDARD.order);
+            }
+            let bid = args.get(2).context("missing bid")?.parse()?;
+            let opponents = args.get(3).context("missing opponent count")?.parse()?;
+            let rounds = args.get(4).map_or(Ok(10000), |s| s.parse())?;
+            let mut rng = sim::Rng::new(args.get(5).map_or(Ok(2023), |s| s.parse())?);
+            for (name, rules) in [("standard", Rules::STANDARD), ("jokers", Rules::JOKERS)] {
+                let expected = sim::expected_winnings(&mut rng, &rules, cards, bid, opponents, rounds)?;
+                println!("{name}: {expected:.2}");
+            }
+        },
+        // --synthetic HANDS [SEED], a random puzzle input
+        Some("--synthetic") => {
+            let hands = args.get(1).context("missing hand count")?.parse()?;
+            let mut rng = sim::Rng::new(args.get(2).map_or(Ok(2023), |s| s.parse())?);
+            print!("{}", sim::synthetic_input(&mut rng, &Rules::STANDARD, hands));
+        },
+        // --order ALPHABET [WILDCARD], for variant decks and hand sizes.
+        // ranks the input with that card order, then again with the wildcard as a joker
+        Some("--order") => {
+            let order = args.get(1).context("missing card order")?;
+            let hands = read_hands(&mut std::io::stdin().lock())?;
+            let rules = Rules { order: Cow::Owned(order.clone()), wildcard: None, wild_type: false, shapes: None };
+            println!("{}", winnings(&hands, &rules)?);
+            if let Some(wildcard) = args.get(2).and_then(|w| w.chars().next()) {
+                // the joker is the weakest card on ties
+                let order = std::iter::once(wildcard).chain(order.chars().filter(|&c| c != wildcard)).collect();
+                let rules = Rules { order: Cow::Owned(order), wildcard: Some(wildcard), wild_type: true, shapes: None };
+                println!("{}", winnings(&hands, &rules)?);
+            }
+        },
+        _ => {
+            go(&mut std::io::stdin().lock())?;
+        },
+    }
+    Ok(())
 }
 
 #[test]
@@ -135,16 +266,119 @@ KK677 28
 KTJJT 220
 QQQJA 483";
 
-    go(&mut testinput.as_bytes())
+    assert_eq!(go(&mut testinput.as_bytes())?, (6440, 5905));
+    Ok(())
 }
 
 #[test]
-fn test1() {
-    let testhand = Hand("32T3K".to_owned());
+fn test1() -> Result<(),Error> {
+    let testhand = Hand::new("32T3K", &Rules::JOKERS)?;
     assert_eq!(testhand.count_cards(), HandType::OnePair);
 
-    let testhand = Hand("T55J5".to_owned());
     // update for joker
-    assert_eq!(testhand.count_cards(), HandType::FourKind);
+    assert_eq!(Hand::new("T55J5", &Rules::JOKERS)?.count_cards(), HandType::FourKind);
+    assert_eq!(Hand::new("T55J5", &Rules::STANDARD)?.count_cards(), HandType::ThreeKind);
+    Ok(())
 }
 
+#[test]
+fn test_rules() -> Result<(),Error> {
+    // J beats T normally, but is the weakest card as a joker
+    assert!(Hand::new("JKKK2", &Rules::STANDARD)? < Hand::new("QQQQ2", &Rules::STANDARD)?);
+    assert!(Hand::new("JKKK2", &Rules::JOKERS)? < Hand::new("QQQQ2", &Rules::JOKERS)?);
+
+    assert!(Hand::new("JJJJJ", &Rules::STANDARD)? > Hand::new("TTTTT", &Rules::STANDARD)?);
+    assert!(Hand::new("JJJJJ", &Rules::JOKERS)? < Hand::new("TTTTT", &Rules::JOKERS)?);
+
+    // a wildcard that only changes the card order
+    let rules = Rules { order: Cow::Borrowed("J23456789TQKA"), wildcard: Some('J'), wild_type: false, shapes: None };
+    assert_eq!(Hand::new("KTJJT", &rules)?.count_cards(), HandType::TwoPair);
+    assert_eq!(Hand::new("KTJJT", &Rules::JOKERS)?.count_cards(), HandType::FourKind);
+    Ok(())
+}
+
+#[test]
+fn test_total_order() -> Result<(),Error> {
+    use std::cmp::Ordering;
+    use std::collections::BTreeMap;
+
+    // every hand over a few cards, including jokers
+    let cards = ['2', 'T', 'J', 'K', 'A'];
+    let mut all = Vec::new();
+    for n in 0..cards.len().pow(5) {
+        let hand: String = (0..5).map(|i| cards[n / cards.len().pow(i) % cards.len()]).collect();
+        all.push(hand);
+    }
+
+    for rules in [Rules::STANDARD, Rules::JOKERS] {
+        let hands: Vec<Hand> = all.iter().step_by(7).map(|cards| Hand::new(cards, &rules)).collect::<Result<_,_>>()?;
+
+        for a in hands.iter() {
+            for b in hands.iter() {
+                // antisymmetric, and equal only for the same cards
+                assert_eq!(a.cmp(b), b.cmp(a).reverse());
+                assert_eq!(a == b, a.cards == b.cards);
+                // type first, then card-by-card
+                let value = |card| cardvalue(card, &rules).unwrap();
+                let expected = a.count_cards().cmp(&b.count_cards())
+                    .then_with(|| a.cards.chars().map(value).cmp(b.cards.chars().map(value)));
+                assert_eq!(a.cmp(b), expected, "{a:?} {b:?}");
+            }
+        }
+
+        // transitive: sorting agrees with every pairwise comparison
+        let mut sorted: Vec<&Hand> = hands.iter().collect();
+        sorted.sort();
+        for (i, a) in sorted.iter().enumerate() {
+            for b in sorted.iter().skip(i+1) {
+                assert_eq!(a.cmp(b), Ordering::Less);
+            }
+        }
+
+        // usable as map keys
+        let map: BTreeMap<&Hand, usize> = hands.iter().map(|h| (h, h.cards.len())).collect();
+        assert_eq!(map.len(), hands.len());
+    }
+    Ok(())
+}
+
+
+#[test]
+fn test_shapes() -> Result<(),Error> {
+    // lexicographic shapes agree with the five card hand types
+    let rules = Rules::STANDARD;
+    let mut shapes: Vec<(u64, HandType)> = [vec![1,1,1,1,1], vec![2,1,1,1], vec![2,2,1], vec![3,1,1], vec![3,2], vec![4,1], vec![5]]
+        .iter().map(|s| (rules.shape_rank(s).unwrap(), HandType::from_shape(s).unwrap())).collect();
+    shapes.sort();
+    assert_eq!(shapes.iter().map(|(_,t)| *t).collect::<Vec<_>>(), HandType::ALL);
+
+    assert_eq!(Hand::new("QJJQ2", &rules)?.shape(), [2,2,1]);
+    assert_eq!(Hand::new("JJJJJ", &Rules::JOKERS)?.shape(), [5]);
+    assert_eq!(Hand::new("QJJQ2", &Rules::JOKERS)?.shape(), [4,1]);
+    Ok(())
+}
+
+#[test]
+fn test_six_cards() -> Result<(),Error> {
+    // an extended alphabet with a second suit of faces, and six card hands
+    let rules = Rules { order: Cow::Borrowed("23456789TJQKAjqka"), wildcard: None, wild_type: false, shapes: None };
+    assert_eq!(Hand::new("QQQqqq", &rules)?.shape(), [3,3]);
+    assert!(Hand::new("QQQqqq", &rules)? < Hand::new("2222a3", &rules)?);
+    assert!(Hand::new("aaaaa2", &rules)? < Hand::new("222222", &rules)?);
+    assert!(Hand::new("22223a", &rules)? < Hand::new("a22223", &rules)?);
+
+    // a variant where two triples beat four of a kind
+    let shapes = vec![vec![1,1,1,1,1,1], vec![2,1,1,1,1], vec![2,2,1,1], vec![2,2,2], vec![3,1,1,1], vec![3,2,1], vec![4,1,1], vec![3,3], vec![4,2], vec![5,1], vec![6]];
+    let rules = Rules { shapes: Some(shapes), ..rules };
+    assert!(Hand::new("QQQqqq", &rules)? > Hand::new("2222a3", &rules)?);
+
+    let input = "QQQqqq 10\n2222a3 1\n";
+    let hands = read_hands(&mut input.as_bytes())?;
+    assert_eq!(winnings(&hands, &rules)?, 1 + 2*10);
+
+    // 17 cards of 4 bits don't fit the key, and neither do unknown cards
+    assert!(Hand::new("22222222222222222", &Rules::STANDARD).is_err());
+    assert_eq!(hand_shape("22222222222222222", &Rules::STANDARD), [17]);
+    assert!(Hand::new("2222X", &Rules::STANDARD).is_err());
+    Ok(())
+}
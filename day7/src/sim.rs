@@ -0,0 +1,150 @@
+// camel cards by the numbers
+// hand type odds, random hands, and bid tournaments against random opponents
+
+use std::collections::BTreeMap;
+use std::fmt::Write;
+
+use anyhow::Error;
+
+use crate::{hand_shape, DEBUG, Hand, HandType, Rules};
+
+// small splitmix64 generator, so runs are repeatable from a seed
+pub struct Rng(u64);
+
+impl Rng {
+    pub fn new(seed: u64) -> Self {
+        Self(seed)
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9E3779B97F4A7C15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58476D1CE4E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D049BB133111EB);
+        z ^ (z >> 31)
+    }
+
+    // uniform in 0..n
+    pub fn below(&mut self, n: usize) -> usize {
+        (self.next_u64() % n as u64) as usize
+    }
+}
+
+pub fn random_hand(rng: &mut Rng, rules: &Rules, size: usize) -> String {
+    let cards: Vec<char> = rules.order.chars().collect();
+    (0..size).map(|_| cards[rng.below(cards.len())]).collect()
+}
+
+// probability of each hand type, by counting every possible five card hand
+pub fn hand_type_odds(rules: &Rules) -> [(HandType, f64); 7] {
+    let cards: Vec<char> = rules.order.chars().collect();
+    let total = cards.len().pow(5);
+    let mut counts = [0usize; 7];
+    let mut hand = String::with_capacity(5);
+    for n in 0..total {
+        hand.clear();
+        let mut rest = n;
+        for _ in 0..5 {
+            hand.push(cards[rest % cards.len()]);
+            rest /= cards.len();
+        }
+        // only the type is needed, so no sort key
+        let shape = hand_shape(&hand, rules);
+        counts[HandType::from_shape(&shape).unwrap_or_else(|| panic!("no type for {hand}, shape {shape:?}")) as usize] += 1;
+    }
+    if DEBUG { eprintln!("hand type counts {counts:?} of {total}") };
+
+    HandType::ALL.map(|t| (t, counts[t as usize] as f64 / total as f64))
+}
+
+// estimate the odds of each hand shape from random hands instead, for any hand size.
+// only shapes are counted, so hands too long for a sort key are fine
+pub fn sample_shape_odds(rng: &mut Rng, rules: &Rules, size: usize, samples: usize) -> BTreeMap<Vec<u32>, f64> {
+    let mut counts = BTreeMap::new();
+    for _ in 0..samples {
+        *counts.entry(hand_shape(&random_hand(rng, rules, size), rules)).or_insert(0usize) += 1;
+    }
+    counts.into_iter().map(|(shape, n)| (shape, n as f64 / samples as f64)).collect()
+}
+
+// average winnings for one hand, ranked among `opponents` random hands over many rounds.
+// the hand's rank is one more than the number of opponents it strictly beats
+pub fn expected_winnings(rng: &mut Rng, rules: &Rules, cards: &str, bid: u32, opponents: usize, rounds: usize) -> Result<f64,Error> {
+    let hand = Hand::new(cards, rules)?;
+    let size = cards.chars().count();
+    let mut total = 0u64;
+    for _ in 0..rounds {
+        let mut beaten = 0;
+        for _ in 0..opponents {
+            if Hand::new(&random_hand(rng, rules, size), rules)? < hand {
+                beaten += 1;
+            }
+        }
+        total += (beaten as u64 + 1) * bid as u64;
+    }
+    let expected = total as f64 / rounds as f64;
+    if DEBUG { eprintln!("{cards} bid {bid} against {opponents}: {expected}") };
+    Ok(expected)
+}
+
+// a random puzzle input of `hands` lines, "32T3K 765"
+pub fn synthetic_input(rng: &mut Rng, rules: &Rules, hands: usize) -> String {
+    let mut input = String::new();
+    for _ in 0..hands {
+        let bid = rng.below(1000) + 1;
+        writeln!(input, "{} {bid}", random_hand(rng, rules, 5)).unwrap();
+    }
+    input
+}
+
+#[test]
+fn test_odds() {
+    let odds = hand_type_odds(&Rules::STANDARD);
+    let total: f64 = odds.iter().map(|(_, p)| p).sum();
+    assert!((total - 1.0).abs() < 1e-9);
+
+    // 13 five of a kinds out of 13^5
+    assert_eq!(odds[HandType::FiveKind as usize], (HandType::FiveKind, 13.0 / 371293.0));
+    // 13*12*11*10*9 all-distinct hands
+    assert!((odds[HandType::HighCard as usize].1 - 154440.0 / 371293.0).abs() < 1e-12);
+
+    // jokers only ever improve a hand
+    let jokers = hand_type_odds(&Rules::JOKERS);
+    assert!(jokers[HandType::HighCard as usize].1 < odds[HandType::HighCard as usize].1);
+    assert!(jokers[HandType::FiveKind as usize].1 > odds[HandType::FiveKind as usize].1);
+
+    // sampling lands near the exact odds
+    let sampled = sample_shape_odds(&mut Rng::new(7), &Rules::JOKERS, 5, 20000);
+    for (shape, estimate) in sampled {
+        let exact = jokers[HandType::from_shape(&shape).unwrap() as usize];
+        assert!((exact.1 - estimate).abs() < 0.02, "{exact:?} {estimate}");
+    }
+
+    // six card hands have their own shapes
+    let sampled = sample_shape_odds(&mut Rng::new(7), &Rules::STANDARD, 6, 1000);
+    assert!(sampled.keys().all(|shape| shape.iter().sum::<u32>() == 6));
+    // and hands too long to key still have shapes
+    let sampled = sample_shape_odds(&mut Rng::new(7), &Rules::STANDARD, 17, 100);
+    assert!(sampled.keys().all(|shape| shape.iter().sum::<u32>() == 17));
+}
+
+#[test]
+fn test_tournament() -> Result<(),Error> {
+    let mut rng = Rng::new(2023);
+    // nothing beats five aces, nothing loses to the weakest high card
+    assert_eq!(expected_winnings(&mut rng, &Rules::STANDARD, "AAAAA", 10, 20, 50)?, 210.0);
+    assert_eq!(expected_winnings(&mut rng, &Rules::STANDARD, "23456", 10, 20, 50)?, 10.0);
+    let middling = expected_winnings(&mut rng, &Rules::STANDARD, "KK677", 10, 20, 200)?;
+    assert!(middling > 10.0 && middling < 210.0);
+    assert!(expected_winnings(&mut rng, &Rules::STANDARD, "AAAAX", 10, 20, 50).is_err());
+    Ok(())
+}
+
+#[test]
+fn test_synthetic() -> Result<(),Error> {
+    let input = synthetic_input(&mut Rng::new(1), &Rules::STANDARD, 50);
+    assert_eq!(input.lines().count(), 50);
+    let (standard, jokers) = crate::go(&mut input.as_bytes())?;
+    assert!(standard > 0 && jokers > 0);
+    Ok(())
+}
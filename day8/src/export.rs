@@ -0,0 +1,135 @@
+// pictures of the desert network
+// graphviz DOT with each ghost's loop colored, and a JSON adjacency dump
+
+use std::collections::HashMap;
+use std::fmt::Write;
+
+use crate::{find_cycle, Network};
+
+const GHOST_COLORS: [&str; 8] = ["red", "blue", "darkgreen", "orange", "purple", "brown", "deeppink", "teal"];
+
+type Colors<K> = HashMap<K, &'static str>;
+
+// the nodes and exits each ghost walks once it is in its loop, by color
+fn cycle_colors(directions:&[u8], network:&Network) -> (Colors<u32>, Colors<(u32,u8)>) {
+    let mut nodes = HashMap::new();
+    let mut edges = HashMap::new();
+    for (ghost, &start) in network.starts.iter().enumerate() {
+        let color = GHOST_COLORS[ghost % GHOST_COLORS.len()];
+        let cycle = find_cycle(start, directions, network);
+        let mut location = start;
+        for step in 0..cycle.tail + cycle.period {
+            let exit = directions[(step % directions.len() as u64) as usize];
+            if step >= cycle.tail {
+                nodes.insert(location, color);
+                edges.insert((location, exit), color);
+            }
+            location = network.exit(location, exit);
+        }
+    }
+    (nodes, edges)
+}
+
+// quoted and escaped, good for both DOT ids and JSON strings
+fn quote(name:&str) -> String {
+    let mut quoted = String::from('"');
+    for c in name.chars() {
+        match c {
+            '"' | '\\' => { quoted.push('\\'); quoted.push(c); },
+            '\n' => quoted.push_str("\\n"),
+            _ => quoted.push(c),
+        }
+    }
+    quoted.push('"');
+    quoted
+}
+
+pub fn to_dot(directions:&[u8], network:&Network, alphabet:&str) -> String {
+    let (cycle_nodes, cycle_edges) = cycle_colors(directions, network);
+    let labels: Vec<char> = alphabet.chars().collect();
+
+    let mut dot = String::from("digraph network {\n");
+    for (id, name) in network.names.iter().enumerate() {
+        let id = id as u32;
+        let mut attrs = Vec::new();
+        if network.starts.contains(&id) {
+            attrs.push(String::from("shape=box, style=filled, fillcolor=palegreen"));
+        } else if network.ends[id as usize] {
+            attrs.push(String::from("shape=doublecircle, style=filled, fillcolor=lightpink"));
+        }
+        if let Some(color) = cycle_nodes.get(&id) {
+            attrs.push(format!("color={color}, penwidth=2"));
+        }
+        if attrs.is_empty() {
+            writeln!(dot, "  {};", quote(name)).unwrap();
+        } else {
+            writeln!(dot, "  {} [{}];", quote(name), attrs.join(", ")).unwrap();
+        }
+    }
+    for (id, name) in network.names.iter().enumerate() {
+        let id = id as u32;
+        for exit in 0..network.width as u8 {
+            let label = labels.get(exit as usize).copied().unwrap_or('?');
+            let to = network.name(network.exit(id, exit));
+            match cycle_edges.get(&(id, exit)) {
+                Some(color) => writeln!(dot, "  {} -> {} [label=\"{label}\", color={color}, penwidth=2];", quote(name), quote(to)).unwrap(),
+                None => writeln!(dot, "  {} -> {} [label=\"{label}\"];", quote(name), quote(to)).unwrap(),
+            }
+        }
+    }
+    dot.push_str("}\n");
+    dot
+}
+
+pub fn to_json(network:&Network, alphabet:&str) -> String {
+    let mut json = String::new();
+    writeln!(json, "{{\n  \"alphabet\": {},\n  \"nodes\": [", quote(alphabet)).unwrap();
+    for (id, name) in network.names.iter().enumerate() {
+        let exits: Vec<String> = (0..network.width as u8).map(|exit| quote(network.name(network.exit(id as u32, exit)))).collect();
+        let comma = if id + 1 < network.names.len() { "," } else { "" };
+        writeln!(json, "    {{\"name\": {}, \"exits\": [{}], \"start\": {}, \"end\": {}}}{comma}",
+            quote(name), exits.join(", "), network.starts.contains(&(id as u32)), network.ends[id]).unwrap();
+    }
+    json.push_str("  ]\n}\n");
+    json
+}
+
+#[cfg(test)]
+const GHOSTS: &str =
+r"LR
+
+11A = (11B, XXX)
+11B = (XXX, 11Z)
+11Z = (11B, XXX)
+22A = (22B, XXX)
+22B = (22C, 22C)
+22C = (22Z, 22Z)
+22Z = (22B, 22B)
+XXX = (XXX, XXX)";
+
+#[test]
+fn test_dot() {
+    let (directions, network) = crate::parse_network(GHOSTS);
+    let dot = to_dot(&directions, &network, "LR");
+    assert!(dot.starts_with("digraph network {\n"));
+    assert!(dot.contains("  \"11A\" [shape=box, style=filled, fillcolor=palegreen];\n"));
+    // 11Z is an end, and in the first ghost's loop
+    assert!(dot.contains("  \"11Z\" [shape=doublecircle, style=filled, fillcolor=lightpink, color=red, penwidth=2];\n"));
+    assert!(dot.contains("  \"11B\" -> \"11Z\" [label=\"R\", color=red, penwidth=2];\n"));
+    assert!(dot.contains("  \"22C\" -> \"22Z\" [label=\"R\", color=blue, penwidth=2];\n"));
+    // nobody loops through XXX
+    assert!(dot.contains("  \"XXX\";\n"));
+    assert!(dot.contains("  \"XXX\" -> \"XXX\" [label=\"L\"];\n"));
+    assert_eq!(dot.matches("->").count(), 2 * network.names.len());
+}
+
+#[test]
+fn test_json() {
+    let (_, network) = crate::parse_network(GHOSTS);
+    let json = to_json(&network, "LR");
+    assert!(json.contains("    {\"name\": \"11A\", \"exits\": [\"11B\", \"XXX\"], \"start\": true, \"end\": false},\n"));
+    // nodes are in the order they were first named
+    assert!(json.contains("    {\"name\": \"XXX\", \"exits\": [\"XXX\", \"XXX\"], \"start\": false, \"end\": false},\n"));
+    assert!(json.ends_with("    {\"name\": \"22Z\", \"exits\": [\"22B\", \"22B\"], \"start\": false, \"end\": true}\n  ]\n}\n"));
+    assert_eq!(quote("a\"b\\c"), "\"a\\\"b\\\\c\"");
+}
@@ -1,7 +1,13 @@
-use std::{io::{Lines, BufRead, BufReader}, collections::HashMap, iter::repeat, thread, sync::Arc};
-use anyhow::{Context,Error};
-use num_integer;
-// use num_bigint::BigUint;
+use std::{io::{Lines, BufRead, BufReader}, collections::HashMap, thread, sync::atomic::{AtomicUsize, Ordering}};
+use anyhow::{anyhow,bail,Context,Error};
+use regex::Regex;
+use std::hash::Hash;
+use num_integer::{self, Integer};
+use num_traits::{CheckedAdd, CheckedMul, CheckedSub, Signed};
+#[cfg(feature = "bigint")]
+use num_bigint::{BigInt, BigUint};
+
+mod export;
 
 const DEBUG:bool = cfg!(debug_assertions);
 
@@ -10,138 +16,525 @@ fn str_to_vec(somestr: &str, skip:usize) -> Vec<u32> {
     somestr.split_ascii_whitespace().skip(skip).map(|s| s.parse().expect("string to be sequence of u32")).collect()
 }
 
-fn loadmap(map:&mut HashMap<String,(String,String)>, line: &String) {
+// placeholder exit for a node that's been referenced but not defined yet
+const UNDEFINED: u32 = u32::MAX;
+
+// how walks pick their start and end nodes
+#[derive(Debug)]
+enum NodeMatch {
+    Exact(String),
+    Suffix(String),
+    Regex(Regex),
+}
+
+impl NodeMatch {
+    // "re:^.A" is a regex, "*A" a suffix, anything else an exact name
+    fn parse(pattern:&str) -> Result<NodeMatch,Error> {
+        if let Some(re) = pattern.strip_prefix("re:") {
+            return Ok(NodeMatch::Regex(Regex::new(re)?));
+        }
+        if let Some(suffix) = pattern.strip_prefix('*') {
+            return Ok(NodeMatch::Suffix(suffix.to_owned()));
+        }
+        return Ok(NodeMatch::Exact(pattern.to_owned()));
+    }
+
+    fn matches(&self, name:&str) -> bool {
+        match self {
+            NodeMatch::Exact(exact) => name == exact,
+            NodeMatch::Suffix(suffix) => name.ends_with(suffix.as_str()),
+            NodeMatch::Regex(re) => re.is_match(name),
+        }
+    }
+}
+
+// the desert network, with node names interned to dense ids.
+// walking it is just indexing, no string hashing
+#[derive(Debug,Default)]
+struct Network {
+    names: Vec<String>,
+    ids: HashMap<String,u32>,
+    // exits per node, set by the first node definition
+    width: usize,
+    // exits of each node, `width` at a time
+    exits: Vec<u32>,
+    // where walks start, and which nodes end them
+    starts: Vec<u32>,
+    ends: Vec<bool>,
+}
+
+impl Network {
+    fn intern(&mut self, name:&str) -> u32 {
+        if let Some(&id) = self.ids.get(name) {
+            return id;
+        }
+        let id = self.names.len() as u32;
+        self.names.push(name.to_owned());
+        self.ids.insert(name.to_owned(), id);
+        self.exits.extend(std::iter::repeat_n(UNDEFINED, self.width));
+        return id;
+    }
+
+    #[cfg(test)]
+    fn id(&self, name:&str) -> Option<u32> {
+        self.ids.get(name).copied()
+    }
+
+    fn name(&self, id:u32) -> &str {
+        &self.names[id as usize]
+    }
+
+    fn exit(&self, id:u32, step:u8) -> u32 {
+        self.exits[id as usize * self.width + step as usize]
+    }
+
+    // check every node is defined
+    fn finish(&mut self) -> Result<(),Error> {
+        if let Some(id) = self.exits.iter().step_by(self.width.max(1)).position(|&e| e == UNDEFINED) {
+            bail!("unknown node {}", self.names[id]);
+        }
+        Ok(())
+    }
+
+    // collect the start and end sets, neither of which can be empty
+    fn select(&mut self, start:&NodeMatch, end:&NodeMatch) -> Result<(),Error> {
+        self.starts = (0..self.names.len() as u32).filter(|&id| start.matches(self.name(id))).collect();
+        self.ends = self.names.iter().map(|n| end.matches(n)).collect();
+        if DEBUG { eprintln!("starts {:?}, {} ends", self.starts, self.ends.iter().filter(|&&e| e).count()) };
+        if self.starts.is_empty() {
+            bail!("no start nodes match {start:?}");
+        }
+        if !self.ends.contains(&true) {
+            bail!("no end nodes match {end:?}");
+        }
+        Ok(())
+    }
+}
+
+fn loadmap(network:&mut Network, line: &str) -> Result<(),Error> {
+
+    let (key, paths) = line.split_once(" = ").with_context(|| format!("missing ' = ' in '{line}'"))?;
+    let paths: Vec<&str> = paths.trim_start_matches('(').trim_end_matches(')').split(", ").collect();
+    if network.width == 0 {
+        network.width = paths.len();
+    } else if paths.len() != network.width {
+        bail!("expected {} exits in '{line}'", network.width);
+    }
+    let id = network.intern(key);
+    for (i, path) in paths.iter().enumerate() {
+        let exit = network.intern(path);
+        network.exits[id as usize * network.width + i] = exit;
+    }
+    Ok(())
+}
+
+// steps as indexes into a node's exits, by their position in the alphabet: L and R, or L, M and R, ...
+fn parse_directions(directions:&str, alphabet:&str) -> Result<Vec<u8>,Error> {
+    directions.chars().map(|step| match alphabet.chars().position(|c| c == step) {
+        Some(exit) => Ok(exit as u8),
+        None => bail!("unknown step {step}"),
+    }).collect()
+}
+
+// the walks to take: instruction alphabet, and start and end nodes
+#[derive(Debug)]
+struct Config {
+    alphabet: String,
+    start: NodeMatch,
+    end: NodeMatch,
+    // threads for the per-ghost walks
+    workers: usize,
+    // use the lcm of each ghost's first end, capped at this many steps, instead of the general solver
+    lcm: bool,
+    max_steps: Option<u64>,
+}
 
-    let (key, paths) = line.split_once(" = ").unwrap();
-    let (left,right) = paths.split_once(", ").unwrap();
-    let left = left.trim_start_matches('(');
-    let right = right.trim_end_matches(')');
-    map.insert(key.to_owned(), (left.to_owned(), right.to_owned()));
+impl Default for Config {
+    // part 2, ghosts go from every ..A to any ..Z
+    fn default() -> Self {
+        Config {
+            alphabet: String::from("LR"),
+            start: NodeMatch::Suffix(String::from("A")),
+            end: NodeMatch::Suffix(String::from("Z")),
+            workers: default_workers(),
+            lcm: false,
+            max_steps: None,
+        }
+    }
 }
 
-fn followmap(directions:&String, maps:&HashMap<String,(String,String)>) -> u32 {
+// in part 1, one walker from the first start
+fn followmap(directions:&[u8], network:&Network) -> u32 {
     let mut steps = 0;
-    let mut location = "AAA";
-    for step in repeat(directions.chars()).flatten() {
-        let fork = maps.get(location).unwrap();
-        location = match step {
-            'L' => fork.0.as_str(),
-            'R' => fork.1.as_str(),
-            _ => panic!("unknown step"),
-        };
+    let mut location = *network.starts.first().expect("no start node");
+    for &step in directions.iter().cycle() {
+        location = network.exit(location, step);
         steps += 1;
-        if DEBUG { eprintln!("{steps}: {step} => {location}") };
-        if location == "ZZZ" { 
+        if DEBUG { eprintln!("{steps}: {step} => {}", network.name(location)) };
+        if network.ends[location as usize] { 
             break; 
         }
     }
     return steps;
 }
 
-// use the lcm of the steps of each leg to figure out when they all line up
-fn followmap_ghost_lcm(directions:Arc<String>, maps:Arc<HashMap<String,(String,String)>>) -> u64 {
-    let locations:Vec<String> = { maps.iter().filter_map(
-        |(k,_v)| {
-            if k.ends_with("A") { Some(k.clone()) } else { None }
-    }).collect()};
-    if DEBUG { eprintln!("starting {locations:?}") };
-
-    //let mut path_steps = Vec::new();
-    let mut handles = Vec::new();
-
-    for start in locations {
-        let arc_directions = directions.clone();
-        let arc_maps = maps.clone();
-        let handle = thread::spawn(move ||{
-            // thread::yield_now();
-            let mut location = &start;
-            let mut steps:u32 = 0;
-            for step in repeat(arc_directions.chars()).flatten() {
-                let fork = arc_maps.get(location).unwrap();
-                location = match step {
-                    'L' => &fork.0,
-                    'R' => &fork.1,
-                    _ => panic!("unknown step"),
-                };
-                steps += 1;
-                // if DEBUG { eprintln!("{steps}: {step} => {location}") };
-                if location.ends_with('Z') { 
-                    if DEBUG { eprintln!("ending: {location}") };
-                    break; 
+// run `walk` for every start on at most `workers` threads, results in start order.
+// a failed walk, or a panicked worker, comes back as an error
+fn on_pool<T:Send>(starts:&[u32], workers:usize, walk:impl Fn(u32) -> Result<T,Error> + Sync) -> Result<Vec<T>,Error> {
+    let next = AtomicUsize::new(0);
+    let finished = thread::scope(|scope| {
+        let handles: Vec<_> = (0..workers.clamp(1, starts.len().max(1))).map(|_| scope.spawn(|| {
+            let mut done = Vec::new();
+            loop {
+                let i = next.fetch_add(1, Ordering::Relaxed);
+                if i >= starts.len() {
+                    break;
                 }
+                done.push((i, walk(starts[i])));
             }
-            return steps;
-        });
+            done
+        })).collect();
+        handles.into_iter().map(|h| h.join()).collect::<Vec<_>>()
+    });
+
+    let mut results: Vec<Option<Result<T,Error>>> = (0..starts.len()).map(|_| None).collect();
+    for done in finished {
+        for (i, result) in done.map_err(|_| anyhow!("ghost walk panicked"))? {
+            results[i] = Some(result);
+        }
+    }
+    results.into_iter().map(|r| r.expect("every start walked")).collect()
+}
+
+fn default_workers() -> usize {
+    thread::available_parallelism().map_or(1, |n| n.get())
+}
 
-        handles.push(handle);
+// steps from start to the first end node. past nodes*directions steps the walk is repeating
+// itself, so that's the default cap
+fn walk_to_end(start:u32, directions:&[u8], network:&Network, max_steps:Option<u64>) -> Result<u64,Error> {
+    let max_steps = max_steps.unwrap_or(network.names.len() as u64 * directions.len() as u64);
+    let mut location = start;
+    let mut steps:u64 = 0;
+    for &step in directions.iter().cycle() {
+        if steps >= max_steps {
+            bail!("ghost starting at {} didn't reach an end node in {max_steps} steps", network.name(start));
+        }
+        location = network.exit(location, step);
+        steps += 1;
+        // if DEBUG { eprintln!("{steps}: {step} => {location}") };
+        if network.ends[location as usize] { 
+            if DEBUG { eprintln!("ending: {}", network.name(location)) };
+            return Ok(steps);
+        }
     }
+    bail!("no directions to follow");
+}
 
-    let path_steps:Vec<u32> = handles.into_iter().map(|h|h.join().unwrap()).collect();
-    if DEBUG { eprintln!("path_steps: {path_steps:?}") };
+// a step count, which the lcm of enough ghosts can push past u64
+#[derive(Debug,PartialEq)]
+enum Steps {
+    Small(u64),
+    #[cfg(feature = "bigint")]
+    Big(BigUint),
+}
 
-    // in case of 64bit overflow.
-    // let all_steps = path_steps.iter().fold(BigUint::from(1u32),|acc,nxt| num_integer::lcm(acc,BigUint::from(*nxt)));
+impl std::fmt::Display for Steps {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Steps::Small(steps) => write!(f, "{steps}"),
+            #[cfg(feature = "bigint")]
+            Steps::Big(steps) => write!(f, "{steps}"),
+        }
+    }
+}
 
-    let all_steps = path_steps.iter().fold(1u64,|acc,nxt| num_integer::lcm(acc,*nxt as u64));
+// lcm of all the path steps, in u64 until it would overflow
+fn lcm_steps(path_steps:&[u64]) -> Result<Steps,Error> {
+    let mut all_steps = 1u64;
+    for (i, &steps) in path_steps.iter().enumerate() {
+        match (all_steps / num_integer::gcd(all_steps, steps)).checked_mul(steps) {
+            Some(lcm) => all_steps = lcm,
+            None => return lcm_steps_overflow(all_steps, &path_steps[i..]),
+        }
+    }
     if DEBUG { eprintln!("all_steps: {all_steps} [{} bits]", 64-all_steps.leading_zeros()) };
-    return all_steps;
+    Ok(Steps::Small(all_steps))
+}
+
+// in case of 64bit overflow, carry on from the partial lcm with BigUint
+#[cfg(feature = "bigint")]
+fn lcm_steps_overflow(partial:u64, rest:&[u64]) -> Result<Steps,Error> {
+    let all_steps = rest.iter().fold(BigUint::from(partial),|acc,nxt| num_integer::lcm(acc,BigUint::from(*nxt)));
+    if DEBUG { eprintln!("all_steps: {all_steps} [{} bits]", all_steps.bits()) };
+    Ok(Steps::Big(all_steps))
+}
+
+#[cfg(not(feature = "bigint"))]
+fn lcm_steps_overflow(partial:u64, rest:&[u64]) -> Result<Steps,Error> {
+    bail!("lcm of {partial} and {rest:?} overflows u64, build with --features bigint");
+}
+
+// use the lcm of the steps of each leg to figure out when they all line up.
+// only right when every ghost loops back to its first end node in exactly that many steps
+fn followmap_ghost_lcm(directions:&[u8], network:&Network, workers:usize, max_steps:Option<u64>) -> Result<Steps,Error> {
+    if DEBUG { eprintln!("starting {:?}", network.starts) };
+
+    let path_steps = on_pool(&network.starts, workers, |start| walk_to_end(start, directions, network, max_steps))?;
+    if DEBUG { eprintln!("path_steps: {path_steps:?}") };
+
+    lcm_steps(&path_steps)
+}
+
+// where one ghost is on an end node, as a function of the step count.
+// the walk's state is (node, instruction index), so it must repeat within nodes*directions steps.
+// after `tail` steps it is in a loop of `period` steps, and stays there.
+#[derive(Debug,PartialEq)]
+struct GhostCycle {
+    tail: u64,
+    period: u64,
+    // end node steps before the loop, each only happens once
+    tail_hits: Vec<u64>,
+    // end node steps in the first pass of the loop, each repeats every period steps
+    cycle_hits: Vec<u64>,
+}
+
+impl GhostCycle {
+    fn hits(&self, step: u64) -> bool {
+        self.tail_hits.contains(&step)
+            || self.cycle_hits.iter().any(|&hit| step >= hit && (step - hit).is_multiple_of(self.period))
+    }
+}
+
+fn find_cycle(start:u32, directions:&[u8], network:&Network) -> GhostCycle {
+    // first step each (node, instruction) state was seen
+    let mut seen = vec![u64::MAX; network.names.len() * directions.len()];
+    let mut ends = Vec::new();
+    let mut location = start;
+    let mut step: u64 = 0;
+    loop {
+        let instruction = (step % directions.len() as u64) as usize;
+        let state = location as usize * directions.len() + instruction;
+        if seen[state] != u64::MAX {
+            let tail = seen[state];
+            let period = step - tail;
+            // this is the state from step tail again, so its hit is already in. unless tail is 0:
+            // the start isn't a hit, and this is the first time the loop's start counts
+            if tail > 0 && ends.last() == Some(&step) {
+                ends.pop();
+            }
+            let (tail_hits, cycle_hits) = ends.into_iter().partition(|&hit| hit < tail);
+            let cycle = GhostCycle { tail, period, tail_hits, cycle_hits };
+            if DEBUG { eprintln!("{}: {cycle:?}", network.name(start)) };
+            return cycle;
+        }
+        seen[state] = step;
+
+        // a hit is landing on an end, so step 0 never is one
+        location = network.exit(location, directions[instruction]);
+        step += 1;
+        if network.ends[location as usize] {
+            ends.push(step);
+        }
+    }
+}
+
+// the arithmetic the chinese remainder theorem needs, checked so i128 can
+// report overflow, which BigInt never does
+trait CrtInt: Integer + Signed + Clone + Hash + From<u64> + CheckedAdd + CheckedSub + CheckedMul {}
+impl<T: Integer + Signed + Clone + Hash + From<u64> + CheckedAdd + CheckedSub + CheckedMul> CrtInt for T {}
+
+#[derive(Debug)]
+struct CrtOverflow;
+
+impl std::fmt::Display for CrtOverflow {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "chinese remainder overflow")
+    }
+}
+
+impl std::error::Error for CrtOverflow {}
+
+// solve x = a1 (mod m1), x = a2 (mod m2) for moduli that needn't be coprime.
+// None if the two can never agree, otherwise x mod lcm(m1,m2)
+fn crt_pair<T:CrtInt>((a1, m1): (T, T), (a2, m2): (T, T)) -> Result<Option<(T, T)>,CrtOverflow> {
+    let egcd = m1.extended_gcd(&m2);
+    let g = egcd.gcd;
+    let diff = a2.checked_sub(&a1).ok_or(CrtOverflow)?;
+    if !diff.is_multiple_of(&g) {
+        return Ok(None);
+    }
+    let lcm = (m1.clone() / g.clone()).checked_mul(&m2).ok_or(CrtOverflow)?;
+    // m1 * x = g (mod m2), so stepping (a2-a1)/g * x periods of m1 from a1 lands on a2 (mod m2)
+    let m2g = m2 / g.clone();
+    let k = (diff / g % m2g.clone()).checked_mul(&(egcd.x % m2g.clone())).ok_or(CrtOverflow)? % m2g;
+    let x = a1.checked_add(&m1.checked_mul(&k).ok_or(CrtOverflow)?).ok_or(CrtOverflow)?.mod_floor(&lcm);
+    Ok(Some((x, lcm)))
+}
+
+// the first step every ghost is on one of its loop hits, trying every choice of loop hit per ghost.
+// choices that land on the same residue are the same from then on, so only the one with the
+// lowest first hit is kept. that still multiplies when the periods share little, so past MAX_CHOICES give up
+fn loop_solution<T:CrtInt>(cycles:&[GhostCycle]) -> Result<Option<T>,Error> {
+    let mut choices: HashMap<(T, T), u64> = HashMap::from([((T::zero(), T::one()), 0)]);
+    for cycle in cycles.iter() {
+        let mut next: HashMap<(T, T), u64> = HashMap::new();
+        for ((x, m), &lowest) in choices.iter() {
+            for &hit in cycle.cycle_hits.iter() {
+                if let Some(residue) = crt_pair((x.clone(), m.clone()), (T::from(hit), T::from(cycle.period)))? {
+                    let first = next.entry(residue).or_insert(u64::MAX);
+                    *first = (*first).min(lowest.max(hit));
+                }
+            }
+        }
+        if next.len() > MAX_CHOICES {
+            bail!("more than {MAX_CHOICES} combinations of loop end hits, try --lcm");
+        }
+        choices = next;
+    }
+    let mut best: Option<T> = None;
+    for ((x, m), lowest) in choices {
+        // first x + n*m at or past every ghost's first loop hit
+        let lowest = T::from(lowest);
+        let step = if x >= lowest {
+            x
+        } else {
+            let periods = (lowest - x.clone()).div_ceil(&m);
+            x.checked_add(&periods.checked_mul(&m).ok_or(CrtOverflow)?).ok_or(CrtOverflow)?
+        };
+        if best.as_ref().is_none_or(|b| step < *b) {
+            best = Some(step);
+        }
+    }
+    Ok(best)
+}
+
+// the loop solution again with BigInt, when i128 wasn't enough
+#[cfg(feature = "bigint")]
+fn loop_solution_overflow(cycles:&[GhostCycle]) -> Result<Option<Steps>,Error> {
+    let best = loop_solution::<BigInt>(cycles)?;
+    if DEBUG { eprintln!("loop solution in BigInt: {best:?}") };
+    Ok(best.map(|step| match u64::try_from(&step) {
+        Ok(step) => Steps::Small(step),
+        Err(_) => Steps::Big(step.to_biguint().expect("steps are positive")),
+    }))
+}
+
+#[cfg(not(feature = "bigint"))]
+fn loop_solution_overflow(_cycles:&[GhostCycle]) -> Result<Option<Steps>,Error> {
+    bail!("step count overflows i128, build with --features bigint");
+}
+
+fn i128_steps(step:i128) -> Result<Steps,Error> {
+    match u64::try_from(step) {
+        Ok(step) => Ok(Steps::Small(step)),
+        #[cfg(feature = "bigint")]
+        Err(_) => Ok(Steps::Big(BigUint::try_from(step)?)),
+        #[cfg(not(feature = "bigint"))]
+        Err(_) => bail!("step count {step} overflows u64, build with --features bigint"),
+    }
+}
+
+// the most combinations of loop hits followmap_ghost_crt will carry between ghosts
+const MAX_CHOICES: usize = 1 << 20;
+
+// the general solution: find each ghost's cycle, then the first step every ghost is on an end.
+// end hits before a ghost's loop are checked directly, loop hits are combined with the chinese remainder theorem
+fn followmap_ghost_crt(directions:&[u8], network:&Network, workers:usize) -> Result<Steps,Error> {
+    let cycles = on_pool(&network.starts, workers, |start| {
+        let cycle = find_cycle(start, directions, network);
+        if cycle.tail_hits.is_empty() && cycle.cycle_hits.is_empty() {
+            bail!("ghost starting at {} never reaches an end node", network.name(start));
+        }
+        Ok(cycle)
+    })?;
+
+    // one-off hits: only possible answers are one ghost's tail hits
+    let tail_best = cycles.iter().flat_map(|c| c.tail_hits.iter().copied())
+        .filter(|&step| cycles.iter().all(|c| c.hits(step)))
+        .min();
+
+    // looping hits, in i128 until that overflows
+    let loop_best = match loop_solution::<i128>(&cycles) {
+        Ok(best) => best.map(i128_steps).transpose()?,
+        Err(e) if e.is::<CrtOverflow>() => loop_solution_overflow(&cycles)?,
+        Err(e) => return Err(e),
+    };
+
+    if DEBUG { eprintln!("cycles: {cycles:?} => {tail_best:?} {loop_best:?}") };
+    match (tail_best, loop_best) {
+        (Some(tail), Some(Steps::Small(step))) => Ok(Steps::Small(tail.min(step))),
+        // a tail hit is always below anything past u64
+        (Some(tail), _) => Ok(Steps::Small(tail)),
+        (None, Some(steps)) => Ok(steps),
+        (None, None) => bail!("the ghosts are never all on end nodes at once"),
+    }
 }
 
 // in part 2, ghosts follow all paths simultaneously
-fn followmap_ghost(directions:&String, maps:&HashMap<String,(String,String)>) -> u32 {
+fn followmap_ghost(directions:&[u8], network:&Network) -> u32 {
     let mut steps = 0;
-    let mut locations:Vec<&String> = maps.iter().filter_map(
-        |(k,_v)| {
-            if k.ends_with("A") { Some(k) } else { None }
-    }).collect();
+    let mut locations = network.starts.clone();
     if DEBUG { eprintln!("starting {locations:?}") };
-    for step in repeat(directions.chars()).flatten() {
-        let mut new_locations = Vec::new();
-        for location in locations {
-            let fork = maps.get(location).unwrap();
-            let new_location = match step {
-                'L' => &fork.0,
-                'R' => &fork.1,
-                _ => panic!("unknown step"),
-            };
-            new_locations.push(new_location);
-        }
-        locations = new_locations;
+    for &step in directions.iter().cycle() {
+        for location in locations.iter_mut() {
+            *location = network.exit(*location, step);
+        }
         steps += 1;
         if DEBUG { eprintln!("{steps}: {step} => {locations:?}") };
-        if locations.iter().all(|loc|loc.ends_with('Z')) { 
+        if locations.iter().all(|&loc| network.ends[loc as usize]) { 
             break; 
         }
     }
     return steps;
 }
 
-fn go(input:&mut dyn BufRead) -> Result<(),Error>{
-    // map navigation
-    // puzzle input, line of directions, lines of path forks
+fn read_input(input:&mut dyn BufRead, config:&Config) -> Result<(Vec<u8>,Network),Error> {
     let mut lines = BufReader::new(input).lines();
 
-    let directions = lines.next().unwrap().unwrap();
+    let directions = lines.next().context("missing directions")??;
 
     if DEBUG { eprintln!("directions: {:?}", &directions) };
 
-    let mut maps = HashMap::new();
+    let mut network = Network::default();
 
-    if ! lines.next().expect("some line").expect("unexpected io error").is_empty() {
-        panic!("next line wasn't empty");
+    if ! lines.next().context("missing blank line")??.is_empty() {
+        bail!("next line wasn't empty");
     }
 
     while let Some(Ok(line)) = lines.next() {
-        loadmap(&mut maps, &line);
+        if line.is_empty() { continue; }
+        loadmap(&mut network, &line)?;
+    }
+    network.finish()?;
+    network.select(&config.start, &config.end)?;
+
+    if DEBUG { eprintln!("pathmap: {:?}", &network) };
+
+    let directions = parse_directions(&directions, &config.alphabet)?;
+    if directions.is_empty() {
+        bail!("no directions to follow");
+    }
+    if let Some(step) = directions.iter().find(|&&step| step as usize >= network.width) {
+        bail!("step {} has no exit, nodes have {}", config.alphabet.chars().nth(*step as usize).unwrap(), network.width);
     }
+    Ok((directions, network))
+}
 
-    if DEBUG { eprintln!("pathmap: {:?}", &maps) };
+fn go(input:&mut dyn BufRead, config:&Config) -> Result<(),Error>{
+    // map navigation
+    // puzzle input, line of directions, lines of path forks
+    let (directions, network) = read_input(input, config)?;
 
     // PART TWO
     eprintln!("PART TWO");
 
     // follow the map steps
-    let steps = followmap_ghost_lcm(Arc::new(directions), Arc::new(maps));
+    let steps = if config.lcm {
+        followmap_ghost_lcm(&directions, &network, config.workers, config.max_steps)?
+    } else {
+        followmap_ghost_crt(&directions, &network, config.workers)?
+    };
 
     // output the steps required
     println!("{steps}");
@@ -150,7 +543,39 @@ fn go(input:&mut dyn BufRead) -> Result<(),Error>{
 }
 
 fn main() -> Result<(),Error> {
-    go(&mut std::io::stdin().lock())
+    // --start PATTERN, --end PATTERN: "AAA" exact, "*A" suffix, "re:^1.A$" regex
+    // --steps ALPHABET: instruction letters, in exit order. defaults to LR
+    // --workers N: threads for the ghost walks
+    // --lcm: the fast answer for inputs whose ghosts loop straight back to their end, --max-steps N caps each walk
+    // dot, json: print the network instead of solving it
+    let mut config = Config::default();
+    let mut command = None;
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        let mut value = || args.next().with_context(|| format!("missing value for {arg}"));
+        match arg.as_str() {
+            "--start" => config.start = NodeMatch::parse(&value()?)?,
+            "--end" => config.end = NodeMatch::parse(&value()?)?,
+            "--steps" => config.alphabet = value()?,
+            "--workers" => config.workers = value()?.parse()?,
+            "--lcm" => config.lcm = true,
+            "--max-steps" => config.max_steps = Some(value()?.parse()?),
+            "dot" | "json" => command = Some(arg),
+            _ => bail!("unknown argument {arg}"),
+        }
+    }
+    match command.as_deref() {
+        Some("dot") => {
+            let (directions, network) = read_input(&mut std::io::stdin().lock(), &config)?;
+            print!("{}", export::to_dot(&directions, &network, &config.alphabet));
+        },
+        Some("json") => {
+            let (_, network) = read_input(&mut std::io::stdin().lock(), &config)?;
+            print!("{}", export::to_json(&network, &config.alphabet));
+        },
+        _ => go(&mut std::io::stdin().lock(), &config)?,
+    }
+    Ok(())
 }
 
 #[test]
@@ -165,7 +590,7 @@ DDD = (DDD, DDD)
 EEE = (EEE, EEE)
 GGG = (GGG, GGG)
 ZZZ = (ZZZ, ZZZ)";
-    go(&mut testinput.as_bytes())
+    go(&mut testinput.as_bytes(), &Config::default())
 }
 
 #[test]
@@ -176,19 +601,41 @@ r"LLR
 AAA = (BBB, BBB)
 BBB = (AAA, ZZZ)
 ZZZ = (ZZZ, ZZZ)";
-    go(&mut testinput.as_bytes())
+    go(&mut testinput.as_bytes(), &Config::default())
 }
 
 #[test]
 fn testinput1() {
-    let mut rmap = HashMap::new();
-    loadmap(&mut rmap, &"AAA = (BBB, CCC)".to_owned());
+    let mut network = Network::default();
+    loadmap(&mut network, "AAA = (BBB, CCC)").unwrap();
+
+    let aaa = network.id("AAA").unwrap();
+    assert_eq!([network.exit(aaa, 0), network.exit(aaa, 1)], [network.id("BBB").unwrap(), network.id("CCC").unwrap()]);
+    assert_eq!(network.names, ["AAA", "BBB", "CCC"]);
+    assert!(network.finish().is_err());
+}
+
+#[test]
+fn test_walks() -> Result<(),Error> {
+    let (directions, network) = read_input(&mut 
+r"LLR
 
-    assert_eq!(rmap.get("AAA"), Some(&("BBB".to_owned(), "CCC".to_owned())));
+AAA = (BBB, BBB)
+BBB = (AAA, ZZZ)
+ZZZ = (ZZZ, ZZZ)".as_bytes(), &Config::default())?;
+    assert_eq!(directions, [0, 0, 1]);
+    assert_eq!(network.starts, [network.id("AAA").unwrap()]);
+    assert_eq!(followmap(&directions, &network), 6);
+    assert_eq!(followmap_ghost(&directions, &network), 6);
+    assert_eq!(followmap_ghost_lcm(&directions, &network, 2, None)?, Steps::Small(6));
+
+    assert!(parse_directions("LRX", "LR").is_err());
+    Ok(())
 }
 
 #[test]
 fn test2() {
+    use std::iter::repeat;
     let res:String = repeat("LR".chars()).flatten().take(20).collect();
     eprintln!("{res}");
     assert_eq!(res, String::from_iter(repeat("LR").take(10)));
@@ -210,5 +657,259 @@ r"LR
 XXX = (XXX, XXX)
 ";
 
-    go(&mut testinput.as_bytes())
-}
\ No newline at end of file
+    go(&mut testinput.as_bytes(), &Config::default())
+}
+#[test]
+fn test_crt() {
+    assert_eq!(crt_pair((2i128, 3), (3, 5)).unwrap(), Some((8, 15)));
+    // shared factors
+    assert_eq!(crt_pair((1i128, 4), (3, 6)).unwrap(), Some((9, 12)));
+    assert_eq!(crt_pair((0i128, 4), (1, 6)).unwrap(), None);
+    assert_eq!(crt_pair((0i128, 1), (5, 7)).unwrap(), Some((5, 7)));
+    // an lcm past i128 is an error, not a wrapped answer
+    let big = i128::MAX / 3;
+    assert!(crt_pair((0, big), (1, big - 1)).is_err());
+}
+
+#[cfg(test)]
+fn parse_network(input:&str) -> (Vec<u8>, Network) {
+    read_input(&mut input.as_bytes(), &Config::default()).unwrap()
+}
+
+#[test]
+fn test_ghost_crt() -> Result<(),Error> {
+    let (directions, network) = parse_network(
+r"LR
+
+11A = (11B, XXX)
+11B = (XXX, 11Z)
+11Z = (11B, XXX)
+22A = (22B, XXX)
+22B = (22C, 22C)
+22C = (22Z, 22Z)
+22Z = (22B, 22B)
+XXX = (XXX, XXX)");
+    assert_eq!(followmap_ghost_crt(&directions, &network, 2)?, Steps::Small(6));
+    assert_eq!(find_cycle(network.id("22A").unwrap(), &directions, &network), GhostCycle { tail: 1, period: 6, tail_hits: vec![], cycle_hits: vec![3, 6] });
+
+    // ends that aren't one cycle length from the start: lcm of first hits (2, 1) is wrong
+    let (directions, network) = parse_network(
+r"L
+
+11A = (11B, 11B)
+11B = (11Z, 11Z)
+11Z = (11C, 11C)
+11C = (11B, 11B)
+22A = (22Z, 22Z)
+22Z = (22B, 22B)
+22B = (22Z, 22Z)");
+    assert_eq!(followmap_ghost_crt(&directions, &network, 2)?, Steps::Small(5));
+    assert_eq!(followmap_ghost(&directions, &network), 5);
+
+    // one ghost passes an end once on the way in
+    let (directions, network) = parse_network(
+r"L
+
+11A = (11Z, 11Z)
+11Z = (11B, 11B)
+11B = (11B, 11B)
+22A = (22Z, 22Z)
+22Z = (22Z, 22Z)");
+    assert_eq!(followmap_ghost_crt(&directions, &network, 2)?, Steps::Small(1));
+
+    // 12 ghosts on all-end loops of 6: 6^12 combinations of hits, but only 6 residues
+    let mut input = String::from("L\n\n");
+    for ghost in 0..12 {
+        input.push_str(&format!("G{ghost}A = (G{ghost}N0Z, G{ghost}N0Z)\n"));
+        for node in 0..6 {
+            let next = (node + 1) % 6;
+            input.push_str(&format!("G{ghost}N{node}Z = (G{ghost}N{next}Z, G{ghost}N{next}Z)\n"));
+        }
+    }
+    let (directions, network) = parse_network(&input);
+    assert_eq!(followmap_ghost_crt(&directions, &network, 2)?, Steps::Small(1));
+    Ok(())
+}
+
+// a ghost per prime, each on a loop of that many nodes with the end node last,
+// so they're all on an end at multiples of the product of the primes
+#[cfg(test)]
+fn prime_rings(primes:&[u64]) -> (Vec<u8>, Network) {
+    let mut input = String::from("L\n\n");
+    for (ghost, &p) in primes.iter().enumerate() {
+        let name = |node:u64| if node == p - 1 { format!("G{ghost}Z") } else { format!("G{ghost}N{node}") };
+        input.push_str(&format!("G{ghost}A = ({0}, {0})\n", name(0)));
+        for node in 0..p {
+            input.push_str(&format!("{0} = ({1}, {1})\n", name(node), name((node + 1) % p)));
+        }
+    }
+    parse_network(&input)
+}
+
+#[test]
+fn test_ghost_crt_overflow() -> Result<(),Error> {
+    // five primes near 2^13 multiply past u64
+    let primes = [8191, 8209, 8219, 8221, 8231];
+    let (directions, network) = prime_rings(&primes);
+    let steps = followmap_ghost_crt(&directions, &network, 2);
+    #[cfg(feature = "bigint")]
+    assert_eq!(steps?, Steps::Big(primes.iter().map(|&p| BigUint::from(p)).product()));
+    #[cfg(not(feature = "bigint"))]
+    assert!(steps.is_err());
+
+    // and ten past i128
+    let primes = [8191, 8209, 8219, 8221, 8231, 8233, 8237, 8243, 8263, 8269];
+    let (directions, network) = prime_rings(&primes);
+    let steps = followmap_ghost_crt(&directions, &network, 2);
+    #[cfg(feature = "bigint")]
+    assert_eq!(steps?, Steps::Big(primes.iter().map(|&p| BigUint::from(p)).product()));
+    #[cfg(not(feature = "bigint"))]
+    assert!(steps.is_err());
+
+    // a few small ones still fit
+    let (directions, network) = prime_rings(&[3, 5, 7]);
+    assert_eq!(followmap_ghost_crt(&directions, &network, 2)?, Steps::Small(105));
+    Ok(())
+}
+
+#[test]
+fn test_ghost_crt_unsolvable() {
+    // one ghost is only on its end at odd steps, the other at even steps
+    let (directions, network) = parse_network(
+r"L
+
+33A = (33Z, 33Z)
+33Z = (33B, 33B)
+33B = (33Z, 33Z)
+44A = (44B, 44B)
+44B = (44Z, 44Z)
+44Z = (44B, 44B)");
+    assert!(followmap_ghost_crt(&directions, &network, 2).is_err());
+
+    // and a ghost that never gets there at all
+    let (directions, network) = parse_network(
+r"L
+
+55A = (55B, 55B)
+55B = (55B, 55B)
+55Z = (55Z, 55Z)");
+    assert!(followmap_ghost_crt(&directions, &network, 2).is_err());
+}
+
+#[test]
+fn test_node_match() -> Result<(),Error> {
+    assert!(NodeMatch::parse("AAA")?.matches("AAA"));
+    assert!(!NodeMatch::parse("AAA")?.matches("BAAA"));
+    assert!(NodeMatch::parse("*Z")?.matches("11Z"));
+    assert!(!NodeMatch::parse("*Z")?.matches("Z11"));
+    assert!(NodeMatch::parse("re:^1.A$")?.matches("11A"));
+    assert!(!NodeMatch::parse("re:^1.A$")?.matches("22A"));
+    assert!(NodeMatch::parse("re:(").is_err());
+
+    // a ghost that starts on its own end, and gets back to it every 2 steps
+    let input = "L\n\nAAA = (BBB, BBB)\nBBB = (AAA, AAA)";
+    let config = Config { start: NodeMatch::parse("AAA")?, end: NodeMatch::parse("AAA")?, ..Config::default() };
+    let (directions, network) = read_input(&mut input.as_bytes(), &config)?;
+    assert_eq!(find_cycle(network.starts[0], &directions, &network), GhostCycle { tail: 0, period: 2, tail_hits: vec![], cycle_hits: vec![2] });
+    assert_eq!(followmap_ghost_crt(&directions, &network, 1)?, Steps::Small(2));
+    assert_eq!(followmap_ghost(&directions, &network), 2);
+
+    // patterns that match nothing are an error, not an answer of 0
+    let input = "L\n\nAAA = (ZZZ, ZZZ)\nZZZ = (ZZZ, ZZZ)";
+    let config = Config { start: NodeMatch::parse("NOPE")?, ..Config::default() };
+    assert!(read_input(&mut input.as_bytes(), &config).is_err());
+    let config = Config { end: NodeMatch::parse("*Q")?, ..Config::default() };
+    assert!(read_input(&mut input.as_bytes(), &config).is_err());
+    assert!(read_input(&mut input.as_bytes(), &Config::default()).is_ok());
+    assert!(read_input(&mut "\n\nAAA = (ZZZ, ZZZ)\nZZZ = (ZZZ, ZZZ)".as_bytes(), &Config::default()).is_err());
+    Ok(())
+}
+
+#[test]
+fn test_three_exits() -> Result<(),Error> {
+    let config = Config { alphabet: String::from("LMR"), start: NodeMatch::parse("re:^S")?, end: NodeMatch::parse("*E")?, ..Config::default() };
+    let (directions, network) = read_input(&mut 
+r"MRL
+
+S1 = (S1, N1, S1)
+N1 = (S1, S1, N2)
+N2 = (N1E, S1, S1)
+N1E = (N1E, N1E, N1E)
+S2 = (N2E, S2, S2)
+N2E = (S2, S2, N2E)".as_bytes(), &config)?;
+    assert_eq!(directions, [1, 2, 0]);
+    assert_eq!(network.width, 3);
+    assert_eq!(network.starts.len(), 2);
+    // S1 -M-> N1 -R-> N2 -L-> N1E, S2 -M-> S2 -R-> S2 -L-> N2E
+    assert_eq!(followmap_ghost(&directions, &network), 3);
+    assert_eq!(followmap_ghost_crt(&directions, &network, 2)?, Steps::Small(3));
+
+    // exits the alphabet can't reach
+    let config = Config { alphabet: String::from("LMRX"), ..config };
+    assert!(read_input(&mut "X\n\nS1 = (S1, S1, S1)".as_bytes(), &config).is_err());
+    Ok(())
+}
+
+#[test]
+fn test_part1_config() -> Result<(),Error> {
+    let config = Config { alphabet: String::from("LR"), start: NodeMatch::parse("AAA")?, end: NodeMatch::parse("ZZZ")?, ..Config::default() };
+    let (directions, network) = read_input(&mut 
+r"RL
+
+AAA = (BBB, CCC)
+BBB = (DDD, EEE)
+CCC = (ZZZ, GGG)
+DDD = (DDD, DDD)
+EEE = (EEE, EEE)
+GGG = (GGG, GGG)
+ZZZ = (ZZZ, ZZZ)".as_bytes(), &config)?;
+    assert_eq!(followmap(&directions, &network), 2);
+    Ok(())
+}
+
+#[test]
+fn test_pool() -> Result<(),Error> {
+    let starts: Vec<u32> = (0..20).collect();
+    // results stay in start order whatever the worker count
+    for workers in [0, 1, 3, 64] {
+        assert_eq!(on_pool(&starts, workers, |s| Ok(s * 2))?, starts.iter().map(|s| s * 2).collect::<Vec<_>>());
+    }
+    assert!(on_pool(&starts, 4, |s| if s == 7 { bail!("bad start") } else { Ok(s) }).is_err());
+    assert!(on_pool(&starts, 4, |s| if s == 7 { panic!("worker dies") } else { Ok(s) }).is_err());
+    assert_eq!(on_pool(&[], 4, Ok)?, Vec::<u32>::new());
+    Ok(())
+}
+
+#[test]
+fn test_lcm_errors() -> Result<(),Error> {
+    // 55A never gets to an end, so its walk gives up instead of spinning
+    let (directions, network) = parse_network(
+r"LR
+
+11A = (11Z, 11Z)
+11Z = (11A, 11A)
+55A = (55B, 55B)
+55B = (55B, 55B)");
+    assert!(followmap_ghost_lcm(&directions, &network, 2, None).is_err());
+    assert!(walk_to_end(network.id("55A").unwrap(), &directions, &network, Some(1000)).is_err());
+    assert_eq!(walk_to_end(network.id("11A").unwrap(), &directions, &network, None)?, 1);
+    assert!(walk_to_end(network.id("11A").unwrap(), &directions, &network, Some(0)).is_err());
+    Ok(())
+}
+
+#[test]
+fn test_lcm_steps() -> Result<(),Error> {
+    assert_eq!(lcm_steps(&[])?, Steps::Small(1));
+    assert_eq!(lcm_steps(&[4, 6, 10])?, Steps::Small(60));
+    // (2^32 - 1)^2 still fits
+    assert_eq!(lcm_steps(&[u32::MAX as u64, u32::MAX as u64 - 1])?, Steps::Small(u32::MAX as u64 * (u32::MAX as u64 - 1)));
+
+    // three primes past 2^32
+    let primes = [4294967311, 4294967357, 4294967371];
+    let result = lcm_steps(&primes);
+    #[cfg(feature = "bigint")]
+    assert_eq!(result?.to_string(), (BigUint::from(primes[0]) * primes[1] * primes[2]).to_string());
+    #[cfg(not(feature = "bigint"))]
+    assert!(result.is_err());
+    Ok(())
+}
@@ -1,64 +1,278 @@
 use std::{io::{BufRead, BufReader}, mem::discriminant};
 
-use anyhow::{Error};
+use anyhow::{bail,Context,Error};
 
 const DEBUG:bool = cfg!(debug_assertions);
 
-fn str_to_vec(somestr: &str, skip:usize) -> Vec<i32> {
-    somestr.split_ascii_whitespace().skip(skip).map(|s| s.parse().expect("string to be sequence of i32")).collect()
+fn str_to_vec(somestr: &str, skip:usize) -> Vec<i64> {
+    somestr.split_ascii_whitespace().skip(skip).map(|s| s.parse().expect("string to be sequence of i64")).collect()
 }
 
-fn get_discriminant_next(sensors:&[i32]) -> i32 {
-    let next:Vec<i32> = sensors.windows(2).map(|sns| sns[1] - sns[0]).collect();
+// why a history can't be extrapolated
+#[derive(Debug,Clone,Copy,PartialEq)]
+enum ExtrapolationError {
+    Empty,
+    // the differences ran out of data before reaching a row of zeros, so there's nothing to say
+    // the next value is right. the best fit is the polynomial through every value, of degree len-1
+    NotPolynomial { best_degree: usize },
+    Overflow,
+}
+
+impl std::fmt::Display for ExtrapolationError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ExtrapolationError::Empty => write!(f, "empty history"),
+            ExtrapolationError::NotPolynomial { best_degree } => write!(f, "insufficient data: differences never reach zero, best fit is degree {best_degree}"),
+            ExtrapolationError::Overflow => write!(f, "extrapolation overflow"),
+        }
+    }
+}
+
+impl std::error::Error for ExtrapolationError {}
+
+// the difference rows reach all zeros while there's still data exactly when the
+// last row, the single (n-1)th difference, is zero:
+//   sum over i of (-1)^(n-1-i) * C(n-1, i) * a[i] = 0
+fn check_polynomial(sensors:&[i64]) -> Result<(),ExtrapolationError> {
+    if sensors.is_empty() {
+        return Err(ExtrapolationError::Empty);
+    }
+    let n = sensors.len() as i128;
+    let mut top: i128 = 0;
+    let mut binomial: i128 = 1; // C(n-1, i)
+    for (i, &sn) in sensors.iter().enumerate() {
+        let i = i as i128;
+        let term = binomial.checked_mul(sn as i128).ok_or(ExtrapolationError::Overflow)?;
+        top = if (n - 1 - i) % 2 == 0 { top.checked_add(term) } else { top.checked_sub(term) }.ok_or(ExtrapolationError::Overflow)?;
+        binomial = binomial.checked_mul(n - 1 - i).ok_or(ExtrapolationError::Overflow)? / (i + 1);
+    }
+    if top != 0 {
+        return Err(ExtrapolationError::NotPolynomial { best_degree: sensors.len() - 1 });
+    }
+    Ok(())
+}
+
+// the original recursion over difference rows, in i64. extrapolate replaced it,
+// it stays as the reference the tests check extrapolate against
+#[cfg(test)]
+fn differences(sensors:&[i64]) -> Result<Vec<i64>,ExtrapolationError> {
+    sensors.windows(2).map(|sns| sns[1].checked_sub(sns[0])).collect::<Option<_>>().ok_or(ExtrapolationError::Overflow)
+}
+
+#[cfg(test)]
+fn get_discriminant_next(sensors:&[i64]) -> Result<i64,ExtrapolationError> {
+    // every difference row of a polynomial is one too, so checking the top row is enough
+    check_polynomial(sensors)?;
+    next_unchecked(sensors)
+}
+
+#[cfg(test)]
+fn next_unchecked(sensors:&[i64]) -> Result<i64,ExtrapolationError> {
+    let next:Vec<i64> = differences(sensors)?;
     if DEBUG { eprintln!(" {sensors:?} => {next:?}") }
     if next.iter().any(|&sn| sn != 0) {
-        let r = sensors[sensors.len()-1] + get_discriminant_next(&next);
-        return r;
+        let r = sensors[sensors.len()-1].checked_add(next_unchecked(&next)?).ok_or(ExtrapolationError::Overflow)?;
+        return Ok(r);
     } else {
         if DEBUG { eprintln!(" {}", sensors[0]) }        
-        return sensors[0];
+        return Ok(sensors[0]);
     }
 }
 
-fn get_discriminant_prev(sensors:&[i32]) -> i32 {
-    let next:Vec<i32> = sensors.windows(2).map(|sns| sns[1] - sns[0]).collect();
+#[cfg(test)]
+fn get_discriminant_prev(sensors:&[i64]) -> Result<i64,ExtrapolationError> {
+    check_polynomial(sensors)?;
+    prev_unchecked(sensors)
+}
+
+#[cfg(test)]
+fn prev_unchecked(sensors:&[i64]) -> Result<i64,ExtrapolationError> {
+    let next:Vec<i64> = differences(sensors)?;
     if DEBUG { eprintln!(" {sensors:?} => {next:?}") }
     if next.iter().any(|&sn| sn != 0) {
-        let r = sensors[0] - get_discriminant_prev(&next);
-        return r;
+        let r = sensors[0].checked_sub(prev_unchecked(&next)?).ok_or(ExtrapolationError::Overflow)?;
+        return Ok(r);
     } else {
         if DEBUG { eprintln!(" {}", sensors[0]) }        
-        return sensors[0];
+        return Ok(sensors[0]);
+    }
+}
+
+// a history as the polynomial through it, in newton form:
+// f(x) = sum over k of (k-th difference at 0) * C(x, k)
+// so it can be evaluated at any index, ahead of the history or behind it
+#[derive(Debug,PartialEq)]
+struct Extrapolation {
+    // first value of each difference row, down to the last row that isn't all zeros
+    leading: Vec<i128>,
+    // length of the history
+    len: usize,
+}
+
+impl Extrapolation {
+    // build the difference table once, with checked arithmetic.
+    // the rows have to reach zeros with data to spare, or it isn't a polynomial we can trust
+    fn new(sensors:&[i64]) -> Result<Self,ExtrapolationError> {
+        if sensors.is_empty() {
+            return Err(ExtrapolationError::Empty);
+        }
+        let mut row: Vec<i128> = sensors.iter().map(|&s| s as i128).collect();
+        let mut leading = Vec::new();
+        while row.iter().any(|&sn| sn != 0) {
+            leading.push(row[0]);
+            row = row.windows(2).map(|sns| sns[1].checked_sub(sns[0])).collect::<Option<_>>()
+                .ok_or(ExtrapolationError::Overflow)?;
+        }
+        if DEBUG { eprintln!(" {sensors:?} => leading differences {leading:?}") }
+        if leading.len() == sensors.len() {
+            return Err(ExtrapolationError::NotPolynomial { best_degree: sensors.len() - 1 });
+        }
+        Ok(Extrapolation { leading, len: sensors.len() })
+    }
+
+    // degree of the polynomial. constant and all-zero histories are degree 0
+    fn degree(&self) -> usize {
+        self.leading.len().saturating_sub(1)
+    }
+
+    // value at any index: 0 is the first value of the history, -1 the one before it
+    fn at(&self, index:i64) -> Option<i128> {
+        let x = index as i128;
+        let mut value: i128 = 0;
+        // C(x, k), generalised to negative x, built up a term at a time.
+        // C(x, k) * (x - k) is always divisible by k + 1
+        let mut binomial: i128 = 1;
+        for (k, &diff) in self.leading.iter().enumerate() {
+            value = value.checked_add(diff.checked_mul(binomial)?)?;
+            binomial = binomial.checked_mul(x - k as i128)? / (k as i128 + 1);
+        }
+        Some(value)
+    }
+
+    // k steps past the end of the history
+    fn ahead(&self, k:i64) -> Option<i128> {
+        self.at((self.len as i64 - 1).checked_add(k)?)
+    }
+
+    // k steps before the start of the history
+    fn behind(&self, k:i64) -> Option<i128> {
+        self.at(k.checked_neg()?)
     }
 }
 
-fn go(input:&mut dyn BufRead) -> Result<(),Error>{
+// next and previous values straight from the history, with no difference rows.
+// for n values of a polynomial of degree < n (which the difference method always assumes),
+//   a[n]  = sum over i of (-1)^(n-1-i) * C(n, i) * a[i]
+//   a[-1] = sum over i of (-1)^i * C(n, i+1) * a[i]
+// one pass, C(n, i+1) built from C(n, i), after check_polynomial makes sure the degree really is < n
+fn extrapolate(sensors:&[i64]) -> Result<(i128,i128),ExtrapolationError> {
+    check_polynomial(sensors)?;
+    let sum = || -> Option<(i128,i128)> {
+        let n = sensors.len() as i128;
+        let mut next: i128 = 0;
+        let mut prev: i128 = 0;
+        let mut binomial: i128 = 1; // C(n, i)
+        for (i, &sn) in sensors.iter().enumerate() {
+            let i = i as i128;
+            let term = binomial.checked_mul(sn as i128)?;
+            next = if (n - 1 - i) % 2 == 0 { next.checked_add(term)? } else { next.checked_sub(term)? };
+            binomial = binomial.checked_mul(n - i)? / (i + 1);
+            let term = binomial.checked_mul(sn as i128)?;
+            prev = if i % 2 == 0 { prev.checked_add(term)? } else { prev.checked_sub(term)? };
+        }
+        Some((next, prev))
+    };
+    sum().ok_or(ExtrapolationError::Overflow)
+}
+
+// report lists each history, steps adds its values that many steps ahead and behind
+fn go(input:&mut dyn BufRead, report: bool, steps: Option<i64>) -> Result<(i128,i128),Error>{
     // Mirage maintenance
     // puzzle input, line of values in a history
     let mut lines = BufReader::new(input).lines();
 
+    if report {
+        print!("{:>6} {:>20} {:>20}", "degree", "next", "previous");
+        if let Some(k) = steps {
+            print!(" {:>20} {:>20}", format!("+{k}"), format!("-{k}"));
+        }
+        println!("  history");
+    }
+
     // "0 3 6 9 12 15"
-    let mut result = 0;
+    let mut result_next = 0;
+    let mut result_prev = 0;
+    let mut failed = 0;
     while let Some(Ok(line)) = lines.next() {
         let sensors = str_to_vec(&line,0);
         // compute the result
-        let order_next = get_discriminant_prev(&sensors);
-        if DEBUG { eprintln!("{sensors:?}, {order_next}") };
-        result += order_next;
+        let (order_next, order_prev) = match extrapolate(&sensors) {
+            Ok(orders) => orders,
+            Err(e) => {
+                // warn, and keep going so every bad history gets reported
+                eprintln!("warning: '{line}': {e}");
+                if report {
+                    print!("{:>6} {:>20} {:>20}", "-", "-", "-");
+                    if steps.is_some() {
+                        print!(" {:>20} {:>20}", "-", "-");
+                    }
+                    println!("  {line}");
+                }
+                failed += 1;
+                continue;
+            },
+        };
+        if DEBUG { eprintln!("{sensors:?}, {order_next}, {order_prev}") };
+        if report {
+            let extrapolation = Extrapolation::new(&sensors)?;
+            print!("{:>6} {order_next:>20} {order_prev:>20}", extrapolation.degree());
+            if let Some(k) = steps {
+                // past i128 is just shown as overflowing, the answers don't need it
+                let show = |value:Option<i128>| value.map_or("overflow".to_string(), |v| v.to_string());
+                print!(" {:>20} {:>20}", show(extrapolation.ahead(k)), show(extrapolation.behind(k)));
+            }
+            println!("  {line}");
+        }
+        result_next += order_next;
+        result_prev += order_prev;
+    }
+    if failed > 0 {
+        bail!("{failed} histories couldn't be extrapolated");
     }
 
     // output the result
-    println!("{result}");
+    println!("{result_next}");
 
-    // PART TWO. 
-    // eprintln!("PART TWO");
-    // println!("{min_loc}");
+    // PART TWO. extrapolate backwards
+    eprintln!("PART TWO");
+    println!("{result_prev}");
 
-    return Ok(());
+    return Ok((result_next, result_prev));
 }
 
 fn main() -> Result<(),Error> {
-    go(&mut std::io::stdin().lock())
+    // --report lists every history with its degree and both extrapolations, ahead of the answers
+    // --steps K adds the values K steps past either end to the report
+    let mut report = false;
+    let mut steps = None;
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--report" => report = true,
+            "--steps" => {
+                let k = args.next().with_context(|| format!("missing value for {arg}"))?;
+                let k: i64 = k.parse().with_context(|| format!("bad step count '{k}'"))?;
+                if k < 0 {
+                    bail!("step count {k} is negative, the report already shows both directions");
+                }
+                steps = Some(k);
+                report = true;
+            },
+            _ => bail!("unknown argument {arg}"),
+        }
+    }
+    go(&mut std::io::stdin().lock(), report, steps)?;
+    Ok(())
 }
 
 #[test]
@@ -67,7 +281,10 @@ fn example() -> Result<(),Error> {
 r"0 3 6 9 12 15
 1 3 6 10 15 21
 10 13 16 21 30 45";
-    go(&mut testinput.as_bytes())
+    assert_eq!(go(&mut testinput.as_bytes(), true, None)?, (114, 2));
+    assert_eq!(go(&mut testinput.as_bytes(), true, Some(3))?, (114, 2));
+    assert_eq!(go(&mut testinput.as_bytes(), true, Some(i64::MAX))?, (114, 2));
+    Ok(())
 }
 
 #[test]
@@ -79,11 +296,114 @@ fn testinput1() {
 #[test]
 fn test2() {
     let v = [1,2,3,4,5];
-    assert_eq!(get_discriminant_next(&v), 6);
+    assert_eq!(get_discriminant_next(&v), Ok(6));
 }
 
 #[test]
 fn test3() {
     let v = [1,2,3,4,5];
-    assert_eq!(get_discriminant_prev(&v), 0);
+    assert_eq!(get_discriminant_prev(&v), Ok(0));
+}
+
+#[test]
+fn test_extrapolation() -> Result<(),Error> {
+    let e = Extrapolation::new(&[10, 13, 16, 21, 30, 45])?;
+    assert_eq!(e.degree(), 3);
+    assert_eq!(e.ahead(1), Some(68));
+    assert_eq!(e.behind(1), Some(5));
+    // every value in the history comes back
+    for (i, &v) in [10, 13, 16, 21, 30, 45].iter().enumerate() {
+        assert_eq!(e.at(i as i64), Some(v));
+    }
+
+    // n^2 + 1, far off in both directions
+    let e = Extrapolation::new(&[1, 2, 5, 10, 17])?;
+    assert_eq!(e.degree(), 2);
+    assert_eq!(e.at(1000), Some(1000 * 1000 + 1));
+    assert_eq!(e.behind(1000), Some(1000 * 1000 + 1));
+
+    let e = Extrapolation::new(&[7, 7, 7])?;
+    assert_eq!((e.degree(), e.ahead(5), e.behind(5)), (0, Some(7), Some(7)));
+    let e = Extrapolation::new(&[0, 0])?;
+    assert_eq!((e.degree(), e.ahead(1)), (0, Some(0)));
+
+    // past i64, in i128
+    let e = Extrapolation::new(&[i64::MAX - 2, i64::MAX - 1, i64::MAX])?;
+    assert_eq!(e.ahead(1), Some(i64::MAX as i128 + 1));
+    // and a clean failure past i128
+    let e = Extrapolation::new(&[0, 1, 8, 27, 64])?;
+    assert_eq!(e.degree(), 3);
+    assert_eq!(e.at(i64::MAX), None);
+    assert_eq!((e.ahead(i64::MAX), e.behind(i64::MIN)), (None, None));
+    Ok(())
+}
+
+#[test]
+fn test_extrapolate() {
+    let examples: [&[i64]; 3] = [&[0, 3, 6, 9, 12, 15], &[1, 3, 6, 10, 15, 21], &[10, 13, 16, 21, 30, 45]];
+    let expected = [(18, -3), (28, 0), (68, 5)];
+    for (sensors, expected) in examples.iter().zip(expected) {
+        assert_eq!(extrapolate(sensors), Ok(expected));
+        assert_eq!((get_discriminant_next(sensors).unwrap() as i128, get_discriminant_prev(sensors).unwrap() as i128), expected);
+    }
+    assert_eq!(extrapolate(&[0]), Ok((0, 0)));
+}
+
+#[test]
+fn test_extrapolate_random() -> Result<(),Error> {
+    // small lcg, so the cases are the same every run
+    let mut seed: u64 = 2023;
+    let mut random = |n: u64| {
+        seed = seed.wrapping_mul(6364136223846793005).wrapping_add(1442695040888963407);
+        (seed >> 33) % n
+    };
+
+    for _ in 0..500 {
+        let len = 1 + random(12) as usize;
+        let sensors: Vec<i64> = if random(2) == 0 {
+            // a polynomial of degree below len, so the difference rows reach zero
+            let degree = random(len as u64);
+            let coefficients: Vec<i64> = (0..=degree).map(|_| random(21) as i64 - 10).collect();
+            (0..len as i64).map(|x| coefficients.iter().rev().fold(0, |acc, c| acc * x + c)).collect()
+        } else {
+            // anything at all
+            (0..len).map(|_| random(2001) as i64 - 1000).collect()
+        };
+
+        let extrapolated = extrapolate(&sensors);
+        assert_eq!(extrapolated.map(|e| e.0), get_discriminant_next(&sensors).map(|n| n as i128), "{sensors:?}");
+        assert_eq!(extrapolated.map(|e| e.1), get_discriminant_prev(&sensors).map(|n| n as i128), "{sensors:?}");
+        match Extrapolation::new(&sensors) {
+            Ok(e) => assert_eq!(Ok((e.ahead(1).unwrap(), e.behind(1).unwrap())), extrapolated, "{sensors:?}"),
+            Err(e) => assert_eq!(Err(e), extrapolated, "{sensors:?}"),
+        }
+    }
+    Ok(())
+}
+
+#[test]
+fn test_not_polynomial() {
+    // 1, 2, 4, 8, 16 doubles: its differences are itself, and never reach zero
+    let doubling = [1, 2, 4, 8, 16];
+    let insufficient = ExtrapolationError::NotPolynomial { best_degree: 4 };
+    assert_eq!(extrapolate(&doubling), Err(insufficient));
+    assert_eq!(get_discriminant_next(&doubling), Err(insufficient));
+    assert_eq!(get_discriminant_prev(&doubling), Err(insufficient));
+    assert_eq!(Extrapolation::new(&doubling).err(), Some(insufficient));
+
+    // two points can't vouch for a line, three can
+    assert_eq!(extrapolate(&[1, 2]), Err(ExtrapolationError::NotPolynomial { best_degree: 1 }));
+    assert_eq!(extrapolate(&[1, 2, 3]), Ok((4, 0)));
+    assert_eq!(extrapolate(&[5]), Err(ExtrapolationError::NotPolynomial { best_degree: 0 }));
+
+    assert_eq!(extrapolate(&[]), Err(ExtrapolationError::Empty));
+    assert_eq!(get_discriminant_next(&[]), Err(ExtrapolationError::Empty));
+    assert_eq!(get_discriminant_prev(&[]), Err(ExtrapolationError::Empty));
+    assert_eq!(Extrapolation::new(&[]).err(), Some(ExtrapolationError::Empty));
+
+    // the old functions stay in i64
+    assert_eq!(get_discriminant_next(&[i64::MAX - 2, i64::MAX - 1, i64::MAX]), Err(ExtrapolationError::Overflow));
+
+    // go reports the bad line rather than adding in a made up number
+    assert!(go(&mut "0 3 6 9 12 15\n1 2 4 8 16\n".as_bytes(), true, Some(2)).is_err());
 }